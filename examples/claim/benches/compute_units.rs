@@ -1,3 +1,14 @@
+//! Compute units of the claim instructions, recorded in compute_units.md.
+//!
+//! Every instruction is run on the lazy entrypoint of contract.toml and on the
+//! standard entrypoint, which is expected in `claim_standard.so`:
+//!
+//! ```sh
+//! SOL_EZ_ENTRYPOINT=standard cargo build-sbf
+//! mv target/deploy/claim.so target/deploy/claim_standard.so
+//! cargo build-sbf
+//! ```
+
 #![feature(test)]
 
 extern crate test;
@@ -25,16 +36,23 @@ fn cu(_b: &mut Bencher) {
     let program_id = Pubkey::new_from_array(claim::ID);
     let manager_id = Pubkey::new_unique();
     let token_id = Pubkey::new_unique();
+    let user_id = Pubkey::new_unique();
 
-    let (config_id, config_bump) = Pubkey::find_program_address(&[b"todo"], &program_id);
-    let (claim_id, claim_bump) = Pubkey::find_program_address(&[b"claim"], &program_id);
+    let (config_id, config_bump) = Pubkey::find_program_address(
+        &ClaimConfig::seeds(manager_id.as_array(), token_id.as_array()),
+        &program_id,
+    );
+    let (claim_id, claim_bump) = Pubkey::find_program_address(
+        &Claim::seeds(
+            manager_id.as_array(),
+            token_id.as_array(),
+            user_id.as_array(),
+        ),
+        &program_id,
+    );
 
     let system_program_id = Pubkey::new_from_array(pinocchio_system::ID);
 
-    let mut mollusk = Mollusk::new(&program_id, "claim");
-    mollusk.add_program(&system_program_id, "solana_system_program", &LOADER_V3);
-    mollusk.logger = Some(Rc::new(RefCell::new(Default::default())));
-
     let manager_account = (manager_id, Account::new(10000000, 0, &system_program_id));
 
     /* *********************************************************************** *
@@ -120,7 +138,7 @@ fn cu(_b: &mut Bencher) {
     let claim_data = (
         Claim::DISCRIMINATOR,   // discriminator
         0u64,                   // amount acquired
-        *claim_id.as_array(),   // cliam auth
+        *user_id.as_array(),    // claim auth
         *manager_id.as_array(), // manager auth
         claim_bump,             // pda bump
     );
@@ -163,13 +181,45 @@ fn cu(_b: &mut Bencher) {
      *  BENCH
      * *********************************************************************** */
 
-    let mut bench = MolluskComputeUnitBencher::new(mollusk)
-        .bench(config_create)
-        .bench(config_update)
-        .bench(claim_update)
+    let mut results = bench(
+        "claim",
+        &program_id,
+        &[config_create, config_update, claim_update],
+    );
+    results.extend(bench(
+        "claim_standard",
+        &program_id,
+        &[
+            ("create_config (standard)", config_create.1, config_create.2),
+            ("update_config (standard)", config_update.1, config_update.2),
+            ("update_claim (standard)", claim_update.1, claim_update.2),
+        ],
+    ));
+
+    write_results(results);
+}
+
+/// Runs the instructions on the program `elf`, splitting the compute units of
+/// the program from the ones of its CPIs.
+fn bench<'a>(
+    elf: &str,
+    program_id: &Pubkey,
+    instructions: &[(&'a str, &'a Instruction, &'a [(Pubkey, Account)])],
+) -> Vec<MyBenchResult<'a>> {
+    let system_program_id = Pubkey::new_from_array(pinocchio_system::ID);
+
+    let mut mollusk = Mollusk::new(program_id, elf);
+    mollusk.add_program(&system_program_id, "solana_system_program", &LOADER_V3);
+    mollusk.logger = Some(Rc::new(RefCell::new(Default::default())));
+
+    let mut bench = instructions
+        .iter()
+        .fold(MolluskComputeUnitBencher::new(mollusk), |bench, ix| {
+            bench.bench(*ix)
+        })
         .must_pass(true);
 
-    let results = bench
+    bench
         .execute_without_write()
         .into_iter()
         .map(|res| {
@@ -206,7 +256,5 @@ fn cu(_b: &mut Bencher) {
                 cpi_cus_consumed: cus_breakdown.as_ref().map(|(_, cu)| *cu),
             }
         })
-        .collect();
-
-    write_results(results);
+        .collect()
 }
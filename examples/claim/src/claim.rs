@@ -1,16 +1,9 @@
 use crate::claim_contract::{
-    Claim, ClaimAccounts, ClaimConfig, ClaimContract, ClaimDispatcher, CreateClaimAccounts,
-    CreateConfigAccounts, UpdateClaimAccounts, UpdateConfigAccounts,
+    Claim, ClaimAccounts, ClaimConfig, ClaimContract, CreateClaimAccounts, CreateConfigAccounts,
+    RedeemVoucherAccounts, UpdateClaimAccounts, UpdateClaimsAccounts, UpdateConfigAccounts,
 };
-use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
-use sol_ez::{
-    account::{AccountReadOnly, AccountSigned, AccountWritable},
-    account_info::{AccountRead, Empty},
-    Contract,
-};
-
-pub const FN: fn(&Pubkey, &[AccountInfo], &[u8]) -> Result<(), ProgramError> =
-    ClaimDispatcher::<MyClaim>::dispatch;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use sol_ez::token;
 
 pub struct MyClaim;
 
@@ -18,19 +11,23 @@ impl ClaimContract for MyClaim {
     #[inline(always)]
     fn create_claim(
         program_id: &Pubkey,
-        mut accounts: CreateClaimAccounts,
+        accounts: &mut CreateClaimAccounts,
         amount: u64,
         claim_authority: [u8; 32],
+        claim_bump: u8,
     ) -> Result<(), ProgramError> {
-        validate_config_manager(accounts.claim_config.as_ref(), &accounts.manager_authority)?;
+        let manager = accounts.manager_authority.key();
+        let token_id = accounts.claim_config.as_ref().token_id;
         accounts.claim.init(
             Claim {
                 amount_acquired: amount,
                 claim_authority,
-                manager_authority: *accounts.manager_authority.key(),
-                bump: 0,
+                manager_authority: *manager,
+                redeemed: 0,
+                bump: claim_bump,
             },
-            0,
+            &Claim::seeds(manager, &token_id, &claim_authority),
+            claim_bump,
             &mut accounts.manager_authority,
             program_id,
         )?;
@@ -40,103 +37,95 @@ impl ClaimContract for MyClaim {
     #[inline(always)]
     fn update_claim(
         _program_id: &Pubkey,
-        mut accounts: UpdateClaimAccounts,
+        accounts: &mut UpdateClaimAccounts,
         amount: u64,
-    ) -> Result<(), ProgramError> {
-        validate_config_manager(accounts.claim_config.as_ref(), &accounts.manager_authority)?;
-        validate_claim_manager(accounts.claim.as_ref(), &accounts.manager_authority)?;
-        accounts.claim.as_ref_mut().amount_acquired += amount;
+    ) -> Result<u64, ProgramError> {
+        accounts
+            .claim
+            .as_ref_mut()
+            .checked_add_amount_acquired(amount)?;
         pinocchio::log::sol_log("testing");
-        accounts.claim.apply()?;
-        Ok(())
+        Ok(accounts.claim.as_ref().amount_acquired)
     }
 
     #[inline(always)]
-    fn claim(_program_id: &Pubkey, accounts: ClaimAccounts) -> Result<(), ProgramError> {
-        validate_claim(
-            accounts.claim.as_ref(),
-            accounts.claim_config.as_ref(),
-            &accounts.claim_authority,
-            &accounts.manager_authority,
+    fn claim(_program_id: &Pubkey, accounts: &mut ClaimAccounts) -> Result<(), ProgramError> {
+        let amount = accounts.claim.as_ref().amount_acquired;
+        let token_id = accounts.claim_config.as_ref().token_id;
+        let manager = *accounts.manager_authority.key();
+        let mut pool_authority =
+            ClaimConfig::signer(&mut accounts.claim_config, &manager, &token_id);
+        token::transfer(
+            &mut accounts.claim_pool,
+            &mut accounts.token_mint,
+            &mut accounts.claim_authority,
+            &mut pool_authority,
+            amount,
         )?;
 
-        // TODO: create CPI call to token transfer to claim tokens
-        // use accounts.user_authority
+        accounts.claim.as_ref_mut().amount_acquired = 0;
         Ok(())
     }
 
     #[inline(always)]
     fn create_config(
         program_id: &Pubkey,
-        mut accounts: CreateConfigAccounts,
+        accounts: &mut CreateConfigAccounts,
         config_bump: u8,
         token_id: Pubkey,
     ) -> Result<(), ProgramError> {
-        accounts.claim_config.init2(
+        let manager = accounts.manager_authority.key();
+        accounts.claim_config.init(
             ClaimConfig {
-                manager_authority: *accounts.manager_authority.key(),
+                manager_authority: *manager,
                 min_amount_to_claim: 0,
                 token_id,
                 bump: config_bump,
             },
+            &ClaimConfig::seeds(manager, &token_id),
             config_bump,
             &mut accounts.manager_authority,
             program_id,
-        )
+        )?;
+        Ok(())
     }
 
-    fn update_config(
+    fn update_claims(
         _program_id: &Pubkey,
-        mut accounts: UpdateConfigAccounts,
+        accounts: &mut UpdateClaimsAccounts,
         amount: u64,
     ) -> Result<(), ProgramError> {
-        validate_config_manager(accounts.claim_config.as_ref(), &accounts.manager_authority)?;
-        accounts.claim_config.as_ref_mut().min_amount_to_claim = amount;
-        accounts.claim_config.apply()?;
-        Ok(())
-    }
-}
-
-#[inline(always)]
-fn validate_config_manager(
-    config: &ClaimConfig,
-    manager: &AccountSigned<Empty, impl AccountRead>,
-) -> Result<(), ProgramError> {
-    if &config.manager_authority != manager.key() {
-        return Err(ProgramError::IllegalOwner);
+        let manager = accounts.manager_authority.key();
+        accounts.remaining.update_each(|claim| {
+            if claim.as_ref().manager_authority != *manager {
+                return Err(ProgramError::IllegalOwner);
+            }
+            claim.as_ref_mut().checked_add_amount_acquired(amount)
+        })
     }
-    Ok(())
-}
 
-#[inline(always)]
-fn validate_claim_manager(
-    claim: &Claim,
-    manager: &AccountSigned<Empty, impl AccountRead>,
-) -> Result<(), ProgramError> {
-    if &claim.manager_authority != manager.key() {
-        return Err(ProgramError::IllegalOwner);
+    fn update_config(
+        _program_id: &Pubkey,
+        accounts: &mut UpdateConfigAccounts,
+        amount: u64,
+    ) -> Result<(), ProgramError> {
+        accounts.claim_config.as_ref_mut().min_amount_to_claim = amount;
+        accounts.claim_config.apply()
     }
-    Ok(())
-}
 
-#[inline(always)]
-fn validate_claim(
-    claim: &Claim,
-    claim_config: &ClaimConfig,
-    claim_auth: &AccountWritable<Empty>,
-    manager: &AccountReadOnly<Empty>,
-) -> Result<(), ProgramError> {
-    if claim_auth.key() != &claim.claim_authority {
-        return Err(ProgramError::IllegalOwner);
-    }
-    if claim_config.min_amount_to_claim > claim.amount_acquired {
-        return Err(ProgramError::Custom(0));
-    }
-    if &claim.manager_authority != manager.key() {
-        return Err(ProgramError::IllegalOwner);
-    }
-    if &claim_config.manager_authority != manager.key() {
-        return Err(ProgramError::IllegalOwner);
+    fn redeem_voucher(
+        _program_id: &Pubkey,
+        accounts: &mut RedeemVoucherAccounts,
+        claim_key: Pubkey,
+        redeemed: u64,
+        amount_to_add: u64,
+    ) -> Result<(), ProgramError> {
+        if accounts.claim.key() != &claim_key || accounts.claim.as_ref().redeemed != redeemed {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let claim = accounts.claim.as_ref_mut();
+        claim.checked_add_amount_acquired(amount_to_add)?;
+        claim.checked_add_redeemed(1)?;
+        Ok(())
     }
-    Ok(())
 }
@@ -2,14 +2,79 @@ use core::marker::PhantomData;
 use borsh::{BorshDeserialize, BorshSerialize};
 use sol_ez::{account::*, account_info::*, AccountData, AccountDataConfig, DataSize};
 use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+pub const ID: Pubkey = [
+    179u8, 46u8, 6u8, 234u8, 186u8, 41u8, 85u8, 58u8, 128u8, 9u8, 215u8, 20u8, 67u8,
+    45u8, 184u8, 177u8, 239u8, 131u8, 165u8, 150u8, 247u8, 244u8, 165u8, 232u8, 124u8,
+    173u8, 97u8, 247u8, 75u8, 127u8, 160u8, 196u8,
+];
+pub fn check_id(id: &Pubkey) -> bool {
+    id == &ID
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ClaimError {
+    /// the claim is below the minimum amount to claim
+    ClaimBelowMinimum = 0u32,
+}
+impl From<ClaimError> for ProgramError {
+    fn from(value: ClaimError) -> Self {
+        ProgramError::Custom(value as u32)
+    }
+}
 #[derive(BorshSerialize, BorshDeserialize, AccountDataConfig)]
 #[account_data(hash(seed = "claim|account|claim", size = 4usize))]
 pub struct Claim {
     pub amount_acquired: u64,
     pub claim_authority: [u8; 32],
     pub manager_authority: [u8; 32],
+    pub redeemed: u64,
     pub bump: u8,
 }
+impl Claim {
+    pub fn seeds<'a>(
+        manager: &'a Pubkey,
+        token: &'a Pubkey,
+        user: &'a Pubkey,
+    ) -> [&'a [u8]; 4usize] {
+        [b"claim".as_slice(), manager.as_slice(), token.as_slice(), user.as_slice()]
+    }
+    pub fn signer<'a, 'info, P>(
+        account: &'a mut Account<'info, AccountData<4usize, Claim>, P, Unsigned>,
+        manager: &'a Pubkey,
+        token: &'a Pubkey,
+        user: &'a Pubkey,
+    ) -> sol_ez::cpi::PdaSigner<'a, 'info, AccountData<4usize, Claim>, P, 4usize>
+    where
+        P: AccountRead,
+    {
+        let bump = account.as_ref().bump;
+        sol_ez::cpi::PdaSigner::new(account, Self::seeds(manager, token, user), bump)
+    }
+}
+impl Claim {
+    #[must_use = "the field is left unchanged on overflow"]
+    pub fn checked_add_amount_acquired(
+        &mut self,
+        value: u64,
+    ) -> Result<(), ProgramError> {
+        sol_ez::math::CheckedField::checked_add_field(&mut self.amount_acquired, value)
+    }
+    #[must_use = "the field is left unchanged on overflow"]
+    pub fn checked_sub_amount_acquired(
+        &mut self,
+        value: u64,
+    ) -> Result<(), ProgramError> {
+        sol_ez::math::CheckedField::checked_sub_field(&mut self.amount_acquired, value)
+    }
+    #[must_use = "the field is left unchanged on overflow"]
+    pub fn checked_add_redeemed(&mut self, value: u64) -> Result<(), ProgramError> {
+        sol_ez::math::CheckedField::checked_add_field(&mut self.redeemed, value)
+    }
+    #[must_use = "the field is left unchanged on overflow"]
+    pub fn checked_sub_redeemed(&mut self, value: u64) -> Result<(), ProgramError> {
+        sol_ez::math::CheckedField::checked_sub_field(&mut self.redeemed, value)
+    }
+}
 #[derive(BorshSerialize, BorshDeserialize, AccountDataConfig)]
 #[account_data(hash(seed = "claim|account|claim_config", size = 4usize))]
 pub struct ClaimConfig {
@@ -18,34 +83,104 @@ pub struct ClaimConfig {
     pub token_id: [u8; 32],
     pub bump: u8,
 }
+impl ClaimConfig {
+    pub fn seeds<'a>(manager: &'a Pubkey, token: &'a Pubkey) -> [&'a [u8]; 3usize] {
+        [b"claim_config".as_slice(), manager.as_slice(), token.as_slice()]
+    }
+    pub fn signer<'a, 'info, P>(
+        account: &'a mut Account<'info, AccountData<4usize, ClaimConfig>, P, Unsigned>,
+        manager: &'a Pubkey,
+        token: &'a Pubkey,
+    ) -> sol_ez::cpi::PdaSigner<'a, 'info, AccountData<4usize, ClaimConfig>, P, 3usize>
+    where
+        P: AccountRead,
+    {
+        let bump = account.as_ref().bump;
+        sol_ez::cpi::PdaSigner::new(account, Self::seeds(manager, token), bump)
+    }
+}
+impl ClaimConfig {
+    #[must_use = "the field is left unchanged on overflow"]
+    pub fn checked_add_min_amount_to_claim(
+        &mut self,
+        value: u64,
+    ) -> Result<(), ProgramError> {
+        sol_ez::math::CheckedField::checked_add_field(
+            &mut self.min_amount_to_claim,
+            value,
+        )
+    }
+    #[must_use = "the field is left unchanged on overflow"]
+    pub fn checked_sub_min_amount_to_claim(
+        &mut self,
+        value: u64,
+    ) -> Result<(), ProgramError> {
+        sol_ez::math::CheckedField::checked_sub_field(
+            &mut self.min_amount_to_claim,
+            value,
+        )
+    }
+}
 pub struct CreateClaimAccounts<'info> {
     pub manager_authority: AccountWritableSigned<'info, Empty>,
     pub claim_config: AccountReadOnly<'info, AccountData<4usize, ClaimConfig>>,
     pub claim: Account<'info, PhantomData<AccountData<4usize, Claim>>, Init, Unsigned>,
+    pub system_program: Program<'info, System>,
 }
 impl<'info> CreateClaimAccounts<'info> {
     pub fn load(
+        program_id: &Pubkey,
         accounts: &'info [pinocchio::account_info::AccountInfo],
     ) -> Result<Self, ProgramError> {
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(0usize, "manager_authority"), (2usize, "claim")],
+            &[(1usize, "claim_config")],
+        )?;
+        let manager_authority = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .mutable()?
+            .signed()?
+            .build()?;
+        let claim_config = AccountBuilder::new(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, ClaimConfig>()
+            .build()?;
+        let claim = Account::new_init(
+            AccountInfo::new_init(
+                accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )?,
+        );
+        let system_program = Program::new(
+            accounts.get(3usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        if claim_config.as_ref().manager_authority != *manager_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
         Ok(Self {
-            manager_authority: AccountBuilder::new(
-                    accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .mutable()?
-                .signed()?
-                .build()?,
-            claim_config: AccountBuilder::new(
-                    accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .set_payload()
-                .build()?,
-            claim: Account::new_init(
-                AccountInfo::new_init(
-                    accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )?,
-            ),
+            manager_authority,
+            claim_config,
+            claim,
+            system_program,
         })
     }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
+    }
 }
 pub struct UpdateClaimAccounts<'info> {
     pub manager_authority: AccountReadOnlySigned<'info, Empty>,
@@ -54,67 +189,202 @@ pub struct UpdateClaimAccounts<'info> {
 }
 impl<'info> UpdateClaimAccounts<'info> {
     pub fn load(
+        program_id: &Pubkey,
         accounts: &'info [pinocchio::account_info::AccountInfo],
     ) -> Result<Self, ProgramError> {
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(2usize, "claim")],
+            &[(0usize, "manager_authority"), (1usize, "claim_config")],
+        )?;
+        let manager_authority = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .signed()?
+            .build()?;
+        let claim_config = AccountBuilder::new(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, ClaimConfig>()
+            .build()?;
+        let claim = AccountBuilder::new(
+                accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, Claim>()
+            .mutable()?
+            .build()?;
+        if claim_config.as_ref().manager_authority != *manager_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
+        if claim.as_ref().manager_authority != *manager_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
         Ok(Self {
-            manager_authority: AccountBuilder::new(
-                    accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .signed()?
-                .build()?,
-            claim_config: AccountBuilder::new(
-                    accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .set_payload()
-                .build()?,
-            claim: AccountBuilder::new(
-                    accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .set_payload()
-                .mutable()?
-                .build()?,
+            manager_authority,
+            claim_config,
+            claim,
         })
     }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        mut self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        self.claim.apply()?;
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
+    }
 }
 pub struct ClaimAccounts<'info> {
-    pub claim_authority: AccountWritable<'info, Empty>,
+    pub claim_authority: AccountWritable<'info, sol_ez::token::TokenAccount>,
     pub claim_config: AccountReadOnly<'info, AccountData<4usize, ClaimConfig>>,
     pub claim: AccountWritable<'info, AccountData<4usize, Claim>>,
     pub manager_authority: AccountReadOnly<'info, Empty>,
-    pub user_authority: AccountReadOnlySigned<'info, Empty>,
+    pub user_authority: AccountWritableSigned<'info, Empty>,
+    pub claim_pool: AccountWritable<'info, sol_ez::token::TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub token_mint: AccountReadOnly<'info, sol_ez::token::Mint>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
 }
 impl<'info> ClaimAccounts<'info> {
+    #[allow(clippy::nonminimal_bool)]
     pub fn load(
+        program_id: &Pubkey,
         accounts: &'info [pinocchio::account_info::AccountInfo],
     ) -> Result<Self, ProgramError> {
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[
+                (0usize, "claim_authority"),
+                (2usize, "claim"),
+                (4usize, "user_authority"),
+                (5usize, "claim_pool"),
+            ],
+            &[(1usize, "claim_config"), (7usize, "token_mint")],
+        )?;
+        let claim_config = AccountBuilder::new(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, ClaimConfig>()
+            .build()?;
+        let claim = AccountBuilder::new(
+                accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, Claim>()
+            .mutable()?
+            .build()?;
+        let manager_authority = AccountBuilder::new(
+                accounts.get(3usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .allow_duplicate()
+            .build()?;
+        let mut user_authority = AccountBuilder::new(
+                accounts.get(4usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .mutable()?
+            .signed()?
+            .build()?;
+        let claim_pool = AccountBuilder::new(
+                accounts.get(5usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .token_payload::<sol_ez::token::TokenAccount>()
+            .mutable()?
+            .build()?;
+        let mut token_program = Program::new(
+            accounts.get(6usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        let mut token_mint = AccountBuilder::new(
+                accounts.get(7usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .token_payload::<sol_ez::token::Mint>()
+            .build()?;
+        let mut system_program = Program::new(
+            accounts.get(8usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        let associated_token_program = Program::new(
+            accounts.get(9usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        let claim_authority = sol_ez::token::AssociatedTokenAccount::new(
+            accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            user_authority.key(),
+            token_mint.key(),
+            &token_program,
+        )?;
+        if claim_pool.as_ref().mint != *token_mint.key() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if claim_pool.as_ref().owner != *claim_config.key() {
+            return Err(ProgramError::IllegalOwner);
+        }
+        if claim_config.as_ref().manager_authority != *manager_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
+        if !(token_mint.key() == &claim_config.as_ref().token_id) {
+            return Err(sol_ez::error::ErrorCode::Constraint.into());
+        }
+        if claim.as_ref().claim_authority != *claim_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
+        if claim.as_ref().manager_authority != *manager_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
+        if !(claim.as_ref().amount_acquired >= claim_config.as_ref().min_amount_to_claim)
+        {
+            return Err(ClaimError::ClaimBelowMinimum.into());
+        }
+        let claim_authority = claim_authority
+            .init_for_payer(
+                &mut token_mint,
+                &mut user_authority,
+                &mut system_program,
+                &mut token_program,
+                &associated_token_program,
+            )?;
+        if claim_authority.as_ref().mint != *token_mint.key() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if claim_authority.as_ref().owner != *user_authority.key() {
+            return Err(ProgramError::IllegalOwner);
+        }
         Ok(Self {
-            claim_authority: AccountBuilder::new(
-                    accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .mutable()?
-                .build()?,
-            claim_config: AccountBuilder::new(
-                    accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .set_payload()
-                .build()?,
-            claim: AccountBuilder::new(
-                    accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .set_payload()
-                .mutable()?
-                .build()?,
-            manager_authority: AccountBuilder::new(
-                    accounts.get(3usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .build()?,
-            user_authority: AccountBuilder::new(
-                    accounts.get(4usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .signed()?
-                .build()?,
+            claim_authority,
+            claim_config,
+            claim,
+            manager_authority,
+            user_authority,
+            claim_pool,
+            token_program,
+            token_mint,
+            system_program,
+            associated_token_program,
         })
     }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        mut self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        self.claim.apply()?;
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
+    }
 }
 pub struct CreateConfigAccounts<'info> {
     pub manager_authority: AccountWritableSigned<'info, Empty>,
@@ -124,25 +394,52 @@ pub struct CreateConfigAccounts<'info> {
         Init,
         Unsigned,
     >,
+    pub system_program: Program<'info, System>,
 }
 impl<'info> CreateConfigAccounts<'info> {
     pub fn load(
+        program_id: &Pubkey,
         accounts: &'info [pinocchio::account_info::AccountInfo],
     ) -> Result<Self, ProgramError> {
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(0usize, "manager_authority"), (1usize, "claim_config")],
+            &[],
+        )?;
+        let manager_authority = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .mutable()?
+            .signed()?
+            .build()?;
+        let claim_config = Account::new_init(
+            AccountInfo::new_init(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )?,
+        );
+        let system_program = Program::new(
+            accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
         Ok(Self {
-            manager_authority: AccountBuilder::new(
-                    accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .mutable()?
-                .signed()?
-                .build()?,
-            claim_config: Account::new_init(
-                AccountInfo::new_init(
-                    accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )?,
-            ),
+            manager_authority,
+            claim_config,
+            system_program,
         })
     }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
+    }
 }
 pub struct UpdateConfigAccounts<'info> {
     pub manager_authority: AccountReadOnlySigned<'info, Empty>,
@@ -150,47 +447,209 @@ pub struct UpdateConfigAccounts<'info> {
 }
 impl<'info> UpdateConfigAccounts<'info> {
     pub fn load(
+        program_id: &Pubkey,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<Self, ProgramError> {
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(1usize, "claim_config")],
+            &[(0usize, "manager_authority")],
+        )?;
+        let manager_authority = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .signed()?
+            .build()?;
+        let claim_config = AccountBuilder::new(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, ClaimConfig>()
+            .mutable()?
+            .build()?;
+        if claim_config.as_ref().manager_authority != *manager_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
+        Ok(Self {
+            manager_authority,
+            claim_config,
+        })
+    }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    ///
+    /// Panics in debug builds if the handler changed a `manual_apply`
+    /// account without applying it.
+    pub fn exit(
+        self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        #[cfg(debug_assertions)]
+        if self.claim_config.is_dirty() {
+            panic!("account claim_config changed but never applied");
+        }
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
+    }
+}
+pub struct UpdateClaimsAccounts<'info> {
+    pub manager_authority: AccountReadOnlySigned<'info, Empty>,
+    pub claim_config: AccountReadOnly<'info, AccountData<4usize, ClaimConfig>>,
+    pub remaining: RemainingAccounts<'info, 4usize, Claim, Mutable>,
+}
+impl<'info> UpdateClaimsAccounts<'info> {
+    pub fn load(
+        program_id: &Pubkey,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<Self, ProgramError> {
+        let remaining = RemainingAccounts::new(accounts, 2usize, program_id);
+        Self::load_with_remaining(program_id, accounts, remaining)
+    }
+    /// Loads the accounts of the instruction, with its remaining
+    /// accounts parsed by the entrypoint or as they are iterated.
+    pub fn load_with_remaining(
+        program_id: &Pubkey,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+        remaining: RemainingAccounts<'info, 4usize, Claim, Mutable>,
+    ) -> Result<Self, ProgramError> {
+        let manager_authority = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .signed()?
+            .build()?;
+        let claim_config = AccountBuilder::new(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, ClaimConfig>()
+            .build()?;
+        if claim_config.as_ref().manager_authority != *manager_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
+        Ok(Self {
+            manager_authority,
+            claim_config,
+            remaining,
+        })
+    }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        self,
+        _accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        let remaining = self.remaining.iterated();
+        drop(self);
+        for account in remaining {
+            sol_ez::account::check_rent_exempt(account)?;
+        }
+        Ok(())
+    }
+}
+pub struct RedeemVoucherAccounts<'info> {
+    pub manager_authority: AccountReadOnly<'info, Empty>,
+    pub claim: AccountWritable<'info, AccountData<4usize, Claim>>,
+    pub instructions: Sysvar<'info, Instructions>,
+}
+impl<'info> RedeemVoucherAccounts<'info> {
+    pub fn load(
+        program_id: &Pubkey,
         accounts: &'info [pinocchio::account_info::AccountInfo],
     ) -> Result<Self, ProgramError> {
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(1usize, "claim")],
+            &[(0usize, "manager_authority")],
+        )?;
+        let manager_authority = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .build()?;
+        let claim = AccountBuilder::new(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, Claim>()
+            .mutable()?
+            .build()?;
+        let instructions = Sysvar::new(
+            accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        if claim.as_ref().manager_authority != *manager_authority.key() {
+            return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+        }
         Ok(Self {
-            manager_authority: AccountBuilder::new(
-                    accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .signed()?
-                .build()?,
-            claim_config: AccountBuilder::new(
-                    accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .set_payload()
-                .mutable()?
-                .build()?,
+            manager_authority,
+            claim,
+            instructions,
         })
     }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        mut self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        self.claim.apply()?;
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
+    }
 }
 pub trait ClaimContract {
+    /// Accounts created with `init` or `init_keypair` are only written by their `apply`.
     fn create_claim(
         program_id: &Pubkey,
-        accounts: CreateClaimAccounts,
+        accounts: &mut CreateClaimAccounts,
         amount: u64,
         claim_authority: [u8; 32],
+        claim_bump: u8,
     ) -> Result<(), ProgramError>;
     fn update_claim(
         program_id: &Pubkey,
-        accounts: UpdateClaimAccounts,
+        accounts: &mut UpdateClaimAccounts,
         amount_to_add: u64,
+    ) -> Result<u64, ProgramError>;
+    fn claim(
+        program_id: &Pubkey,
+        accounts: &mut ClaimAccounts,
     ) -> Result<(), ProgramError>;
-    fn claim(program_id: &Pubkey, accounts: ClaimAccounts) -> Result<(), ProgramError>;
+    /// Accounts created with `init` or `init_keypair` are only written by their `apply`.
     fn create_config(
         program_id: &Pubkey,
-        accounts: CreateConfigAccounts,
+        accounts: &mut CreateConfigAccounts,
         config_bump: u8,
         token_id: [u8; 32],
     ) -> Result<(), ProgramError>;
+    /// `claim_config` must be applied by the handler, see `manual_apply`.
     fn update_config(
         program_id: &Pubkey,
-        accounts: UpdateConfigAccounts,
+        accounts: &mut UpdateConfigAccounts,
         min_amount_to_claim: u64,
     ) -> Result<(), ProgramError>;
+    /// The remaining accounts are only written by their `apply`, see `update_each`.
+    fn update_claims(
+        program_id: &Pubkey,
+        accounts: &mut UpdateClaimsAccounts,
+        amount_to_add: u64,
+    ) -> Result<(), ProgramError>;
+    fn redeem_voucher(
+        program_id: &Pubkey,
+        accounts: &mut RedeemVoucherAccounts,
+        claim_key: [u8; 32],
+        redeemed: u64,
+        amount_to_add: u64,
+    ) -> Result<(), ProgramError>;
 }
 pub struct ClaimDispatcher<T> {
     inner: PhantomData<T>,
@@ -200,6 +659,8 @@ pub const UPDATE_CLAIM: [u8; 4usize] = [3u8, 255u8, 115u8, 253u8];
 pub const CLAIM: [u8; 4usize] = [29u8, 37u8, 118u8, 180u8];
 pub const CREATE_CONFIG: [u8; 4usize] = [78u8, 77u8, 163u8, 125u8];
 pub const UPDATE_CONFIG: [u8; 4usize] = [88u8, 6u8, 10u8, 242u8];
+pub const UPDATE_CLAIMS: [u8; 4usize] = [36u8, 136u8, 250u8, 32u8];
+pub const REDEEM_VOUCHER: [u8; 4usize] = [172u8, 86u8, 123u8, 63u8];
 impl<T> sol_ez::Contract for ClaimDispatcher<T>
 where
     T: ClaimContract,
@@ -210,32 +671,392 @@ where
         payload: &[u8],
     ) -> Result<(), ProgramError> {
         let ix_data = sol_ez::InstructionData::new(payload)?;
-        match ix_data.ix {
-            &CREATE_CLAIM => {
-                let accounts = CreateClaimAccounts::load(accounts)?;
-                let (amount, claim_authority) = ix_data.deserialize_data()?;
-                T::create_claim(program_id, accounts, amount, claim_authority)
+        match *ix_data.ix {
+            CREATE_CLAIM => {
+                let mut ix_accounts = CreateClaimAccounts::load(program_id, accounts)?;
+                let (amount, claim_authority, claim_bump) = ix_data.deserialize_data()?;
+                T::create_claim(
+                    program_id,
+                    &mut ix_accounts,
+                    amount,
+                    claim_authority,
+                    claim_bump,
+                )?;
+                ix_accounts.exit(accounts)
             }
-            &UPDATE_CLAIM => {
-                let accounts = UpdateClaimAccounts::load(accounts)?;
+            UPDATE_CLAIM => {
+                let mut ix_accounts = UpdateClaimAccounts::load(program_id, accounts)?;
                 let amount_to_add = ix_data.deserialize_data()?;
-                T::update_claim(program_id, accounts, amount_to_add)
+                let value = T::update_claim(
+                    program_id,
+                    &mut ix_accounts,
+                    amount_to_add,
+                )?;
+                ix_accounts.exit(accounts)?;
+                sol_ez::cpi::set_return_data(&value)
             }
-            &CLAIM => {
-                let accounts = ClaimAccounts::load(accounts)?;
-                T::claim(program_id, accounts)
+            CLAIM => {
+                let mut ix_accounts = ClaimAccounts::load(program_id, accounts)?;
+                T::claim(program_id, &mut ix_accounts)?;
+                ix_accounts.exit(accounts)
             }
-            &CREATE_CONFIG => {
-                let accounts = CreateConfigAccounts::load(accounts)?;
+            CREATE_CONFIG => {
+                let mut ix_accounts = CreateConfigAccounts::load(program_id, accounts)?;
                 let (config_bump, token_id) = ix_data.deserialize_data()?;
-                T::create_config(program_id, accounts, config_bump, token_id)
+                T::create_config(program_id, &mut ix_accounts, config_bump, token_id)?;
+                ix_accounts.exit(accounts)
             }
-            &UPDATE_CONFIG => {
-                let accounts = UpdateConfigAccounts::load(accounts)?;
+            UPDATE_CONFIG => {
+                let mut ix_accounts = UpdateConfigAccounts::load(program_id, accounts)?;
                 let min_amount_to_claim = ix_data.deserialize_data()?;
-                T::update_config(program_id, accounts, min_amount_to_claim)
+                T::update_config(program_id, &mut ix_accounts, min_amount_to_claim)?;
+                ix_accounts.exit(accounts)
+            }
+            UPDATE_CLAIMS => {
+                let mut ix_accounts = UpdateClaimsAccounts::load(program_id, accounts)?;
+                let amount_to_add = ix_data.deserialize_data()?;
+                T::update_claims(program_id, &mut ix_accounts, amount_to_add)?;
+                ix_accounts.exit(accounts)
+            }
+            REDEEM_VOUCHER => {
+                let mut ix_accounts = RedeemVoucherAccounts::load(program_id, accounts)?;
+                sol_ez::precompile::verify_ed25519(
+                    &ix_accounts.instructions,
+                    ix_accounts.manager_authority.key(),
+                    payload,
+                )?;
+                let (claim_key, redeemed, amount_to_add) = ix_data.deserialize_data()?;
+                T::redeem_voucher(
+                    program_id,
+                    &mut ix_accounts,
+                    claim_key,
+                    redeemed,
+                    amount_to_add,
+                )?;
+                ix_accounts.exit(accounts)
             }
             _ => Err(ProgramError::InvalidInstructionData),
         }
     }
+    fn dispatch_lazy(
+        mut context: pinocchio::entrypoint::InstructionContext,
+    ) -> Result<(), ProgramError> {
+        let (program_id, payload) = sol_ez::entrypoint::peek_instruction(&context)?;
+        let ix_data = sol_ez::InstructionData::new(payload)?;
+        match *ix_data.ix {
+            CREATE_CLAIM => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 4usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = CreateClaimAccounts::load(program_id, accounts)?;
+                let (amount, claim_authority, claim_bump) = ix_data.deserialize_data()?;
+                T::create_claim(
+                    program_id,
+                    &mut ix_accounts,
+                    amount,
+                    claim_authority,
+                    claim_bump,
+                )?;
+                ix_accounts.exit(accounts)
+            }
+            UPDATE_CLAIM => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 3usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = UpdateClaimAccounts::load(program_id, accounts)?;
+                let amount_to_add = ix_data.deserialize_data()?;
+                let value = T::update_claim(
+                    program_id,
+                    &mut ix_accounts,
+                    amount_to_add,
+                )?;
+                ix_accounts.exit(accounts)?;
+                sol_ez::cpi::set_return_data(&value)
+            }
+            CLAIM => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 10usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = ClaimAccounts::load(program_id, accounts)?;
+                T::claim(program_id, &mut ix_accounts)?;
+                ix_accounts.exit(accounts)
+            }
+            CREATE_CONFIG => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 3usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = CreateConfigAccounts::load(program_id, accounts)?;
+                let (config_bump, token_id) = ix_data.deserialize_data()?;
+                T::create_config(program_id, &mut ix_accounts, config_bump, token_id)?;
+                ix_accounts.exit(accounts)
+            }
+            UPDATE_CONFIG => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 2usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = UpdateConfigAccounts::load(program_id, accounts)?;
+                let min_amount_to_claim = ix_data.deserialize_data()?;
+                T::update_config(program_id, &mut ix_accounts, min_amount_to_claim)?;
+                ix_accounts.exit(accounts)
+            }
+            UPDATE_CLAIMS => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; pinocchio::MAX_TX_ACCOUNTS];
+                let (fixed, rest) = parsed.split_at_mut(2usize);
+                let accounts = sol_ez::entrypoint::parse_accounts(&mut context, fixed)?;
+                let remaining = RemainingAccounts::lazy(
+                    context,
+                    accounts,
+                    rest,
+                    program_id,
+                );
+                let mut ix_accounts = UpdateClaimsAccounts::load_with_remaining(
+                    program_id,
+                    accounts,
+                    remaining,
+                )?;
+                let amount_to_add = ix_data.deserialize_data()?;
+                T::update_claims(program_id, &mut ix_accounts, amount_to_add)?;
+                ix_accounts.exit(accounts)
+            }
+            REDEEM_VOUCHER => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 3usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = RedeemVoucherAccounts::load(program_id, accounts)?;
+                sol_ez::precompile::verify_ed25519(
+                    &ix_accounts.instructions,
+                    ix_accounts.manager_authority.key(),
+                    payload,
+                )?;
+                let (claim_key, redeemed, amount_to_add) = ix_data.deserialize_data()?;
+                T::redeem_voucher(
+                    program_id,
+                    &mut ix_accounts,
+                    claim_key,
+                    redeemed,
+                    amount_to_add,
+                )?;
+                ix_accounts.exit(accounts)
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+pub mod cpi {
+    use super::*;
+    use sol_ez::cpi::{CpiAccount, CpiMeta, CpiSigner, CpiWritable};
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_claim<'info>(
+        manager_authority: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        claim_config: &mut impl CpiAccount<'info>,
+        claim: &mut impl CpiWritable<'info>,
+        system_program: &mut Program<'info, System>,
+        amount: u64,
+        claim_authority: [u8; 32],
+        claim_bump: u8,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 45usize];
+        data[..CREATE_CLAIM.len()].copy_from_slice(&CREATE_CLAIM);
+        sol_ez::cpi::serialize_args(
+            &mut data[CREATE_CLAIM.len()..],
+            &(amount, claim_authority, claim_bump),
+        )?;
+        sol_ez::cpi::invoke(
+            &ID,
+            [
+                CpiMeta::writable_signer(manager_authority),
+                CpiMeta::readonly(claim_config),
+                CpiMeta::writable(claim),
+                CpiMeta::readonly(system_program),
+            ],
+            &data,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_claim<'info>(
+        manager_authority: &mut impl CpiSigner<'info>,
+        claim_config: &mut impl CpiAccount<'info>,
+        claim: &mut impl CpiWritable<'info>,
+        amount_to_add: u64,
+    ) -> Result<u64, ProgramError> {
+        let mut data = [0; 12usize];
+        data[..UPDATE_CLAIM.len()].copy_from_slice(&UPDATE_CLAIM);
+        sol_ez::cpi::serialize_args(&mut data[UPDATE_CLAIM.len()..], &(amount_to_add,))?;
+        sol_ez::cpi::invoke(
+            &ID,
+            [
+                CpiMeta::readonly_signer(manager_authority),
+                CpiMeta::readonly(claim_config),
+                CpiMeta::writable(claim),
+            ],
+            &data,
+        )?;
+        sol_ez::cpi::get_return_data(&ID)
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn claim<'info>(
+        claim_authority: &mut impl CpiWritable<'info>,
+        claim_config: &mut impl CpiAccount<'info>,
+        claim: &mut impl CpiWritable<'info>,
+        manager_authority: &mut impl CpiAccount<'info>,
+        user_authority: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        claim_pool: &mut impl CpiWritable<'info>,
+        token_program: &mut Program<'info, Token>,
+        token_mint: &mut impl CpiAccount<'info>,
+        system_program: &mut Program<'info, System>,
+        associated_token_program: &mut Program<'info, AssociatedToken>,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 4usize];
+        data[..CLAIM.len()].copy_from_slice(&CLAIM);
+        sol_ez::cpi::invoke(
+            &ID,
+            [
+                CpiMeta::writable(claim_authority),
+                CpiMeta::readonly(claim_config),
+                CpiMeta::writable(claim),
+                CpiMeta::readonly(manager_authority),
+                CpiMeta::writable_signer(user_authority),
+                CpiMeta::writable(claim_pool),
+                CpiMeta::readonly(token_program),
+                CpiMeta::readonly(token_mint),
+                CpiMeta::readonly(system_program),
+                CpiMeta::readonly(associated_token_program),
+            ],
+            &data,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_config<'info>(
+        manager_authority: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        claim_config: &mut impl CpiWritable<'info>,
+        system_program: &mut Program<'info, System>,
+        config_bump: u8,
+        token_id: [u8; 32],
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 37usize];
+        data[..CREATE_CONFIG.len()].copy_from_slice(&CREATE_CONFIG);
+        sol_ez::cpi::serialize_args(
+            &mut data[CREATE_CONFIG.len()..],
+            &(config_bump, token_id),
+        )?;
+        sol_ez::cpi::invoke(
+            &ID,
+            [
+                CpiMeta::writable_signer(manager_authority),
+                CpiMeta::writable(claim_config),
+                CpiMeta::readonly(system_program),
+            ],
+            &data,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_config<'info>(
+        manager_authority: &mut impl CpiSigner<'info>,
+        claim_config: &mut impl CpiWritable<'info>,
+        min_amount_to_claim: u64,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 12usize];
+        data[..UPDATE_CONFIG.len()].copy_from_slice(&UPDATE_CONFIG);
+        sol_ez::cpi::serialize_args(
+            &mut data[UPDATE_CONFIG.len()..],
+            &(min_amount_to_claim,),
+        )?;
+        sol_ez::cpi::invoke(
+            &ID,
+            [
+                CpiMeta::readonly_signer(manager_authority),
+                CpiMeta::writable(claim_config),
+            ],
+            &data,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_claims<'info>(
+        manager_authority: &mut impl CpiSigner<'info>,
+        claim_config: &mut impl CpiAccount<'info>,
+        remaining: &mut [impl CpiWritable<'info>],
+        amount_to_add: u64,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 12usize];
+        data[..UPDATE_CLAIMS.len()].copy_from_slice(&UPDATE_CLAIMS);
+        sol_ez::cpi::serialize_args(
+            &mut data[UPDATE_CLAIMS.len()..],
+            &(amount_to_add,),
+        )?;
+        sol_ez::cpi::invoke_with_remaining(
+            &ID,
+            [
+                CpiMeta::readonly_signer(manager_authority),
+                CpiMeta::readonly(claim_config),
+            ],
+            remaining,
+            true,
+            &data,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn redeem_voucher<'info>(
+        manager_authority: &mut impl CpiAccount<'info>,
+        claim: &mut impl CpiWritable<'info>,
+        instructions: &mut Sysvar<'info, Instructions>,
+        claim_key: [u8; 32],
+        redeemed: u64,
+        amount_to_add: u64,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 52usize];
+        data[..REDEEM_VOUCHER.len()].copy_from_slice(&REDEEM_VOUCHER);
+        sol_ez::cpi::serialize_args(
+            &mut data[REDEEM_VOUCHER.len()..],
+            &(claim_key, redeemed, amount_to_add),
+        )?;
+        sol_ez::cpi::invoke(
+            &ID,
+            [
+                CpiMeta::readonly(manager_authority),
+                CpiMeta::writable(claim),
+                CpiMeta::readonly(instructions),
+            ],
+            &data,
+        )
+    }
 }
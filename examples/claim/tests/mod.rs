@@ -1,14 +1,26 @@
 use borsh::BorshDeserialize;
-use claim::{ClaimConfig, ClaimDispatcher, MyClaim, CREATE_CONFIG, UPDATE_CONFIG};
+use claim::{
+    Claim, ClaimAccounts, ClaimConfig, ClaimContract, ClaimDispatcher, CreateClaimAccounts,
+    CreateConfigAccounts, MyClaim, RedeemVoucherAccounts, UpdateClaimAccounts,
+    UpdateClaimsAccounts, UpdateConfigAccounts, CREATE_CONFIG, UPDATE_CLAIM, UPDATE_CLAIMS,
+    UPDATE_CONFIG,
+};
 use pinocchio::{
     account_info::AccountInfo,
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::{self, Pubkey},
     runtime::mock::{invoke, MockAccount, MockProgramAccount, MOCK_RUNTIME},
+    sysvars::{rent::Rent, Sysvar},
     ProgramResult,
 };
-use sol_ez::{AccountData, AccountDataConfig, Contract, InstructionData};
+use sol_ez::{
+    account::{AccountBuilder, AccountWritable, AccountWritableSigned},
+    account_info::Empty,
+    cpi::get_return_data,
+    error::ErrorCode,
+    transfer, AccountData, AccountDataConfig, Contract, InstructionData,
+};
 
 fn system_program(
     _program_id: &Pubkey,
@@ -22,26 +34,65 @@ fn system_program(
             let (lamports, space, owner) = ix_data.deserialize_data()?;
             let lamports = u64::from_le_bytes(lamports);
             let space = u64::from_le_bytes(space);
+            // an account that already holds lamports is in use
+            if account_infos[1].lamports() > 0 {
+                return Err(ProgramError::Custom(0));
+            }
             unsafe {
+                *account_infos[0].borrow_mut_lamports_unchecked() -= lamports;
                 *account_infos[1].borrow_mut_lamports_unchecked() = lamports;
                 account_infos[1].realloc(space as usize, false)?;
                 account_infos[1].assign(&owner);
             }
             Ok(())
         }
+        // assign
+        [1, 0, 0, 0] => {
+            let owner: Pubkey = ix_data.deserialize_data()?;
+            unsafe { account_infos[0].assign(&owner) };
+            Ok(())
+        }
+        // transfer
+        [2, 0, 0, 0] => {
+            let lamports = u64::from_le_bytes(ix_data.deserialize_data()?);
+            unsafe {
+                *account_infos[0].borrow_mut_lamports_unchecked() -= lamports;
+                *account_infos[1].borrow_mut_lamports_unchecked() += lamports;
+            }
+            Ok(())
+        }
+        // allocate
+        [8, 0, 0, 0] => {
+            let space = u64::from_le_bytes(ix_data.deserialize_data()?);
+            account_infos[0].realloc(space as usize, false)
+        }
         _ => Err(ProgramError::InvalidInstructionData),
     }
 }
 
 #[test]
 fn create_config() {
+    assert_create_config(0);
+}
+
+/// Lamports sent to the config address before it is created must not block
+/// its creation.
+#[test]
+fn create_prefunded_config() {
+    assert_create_config(1_000);
+}
+
+fn assert_create_config(prefunded_lamports: u64) {
+    const MANAGER_LAMPORTS: u64 = 10_000_000;
+
     let program_id = [250; 32];
     let token_id = [150; 32];
     let manager_id = [50; 32];
 
-    let (config_id, config_id_bump) = pubkey::find_program_address(&[b"todo"], &program_id);
+    let (config_id, config_id_bump) =
+        pubkey::find_program_address(&[b"claim_config", &manager_id, &token_id], &program_id);
 
-    let (manager, claim_config) = MOCK_RUNTIME.with_borrow_mut(|rt| {
+    let (manager, claim_config, system_program_account) = MOCK_RUNTIME.with_borrow_mut(|rt| {
         rt.register_program_account(
             "my_claim",
             MockProgramAccount::new_program(
@@ -68,16 +119,42 @@ fn create_config() {
 
         rt.register_data_account(
             "manager_account",
-            MockAccount::new_data_account(true, true, manager_id, manager_id, 0, vec![]),
+            MockAccount::new_data_account(
+                true,
+                true,
+                manager_id,
+                pinocchio_system::ID,
+                MANAGER_LAMPORTS,
+                vec![],
+            ),
         );
         rt.register_data_account(
             "claim_config",
-            MockAccount::new_data_account(false, true, config_id, program_id, 0, vec![]),
+            MockAccount::new_data_account(
+                false,
+                true,
+                config_id,
+                pinocchio_system::ID,
+                prefunded_lamports,
+                vec![],
+            ),
+        );
+        rt.register_data_account(
+            "system_program_account",
+            MockAccount::new_data_account(
+                false,
+                false,
+                pinocchio_system::ID,
+                pinocchio_system::ID,
+                0,
+                vec![],
+            ),
         );
 
         (
             rt.get_data_account(&manager_id).unwrap(),
             rt.get_data_account(&config_id).unwrap(),
+            rt.get_data_account(&pinocchio_system::ID).unwrap(),
         )
     });
 
@@ -88,6 +165,7 @@ fn create_config() {
     let accounts = [
         AccountMeta::writable_signer(&manager_id),
         AccountMeta::writable(&config_id),
+        AccountMeta::readonly(&pinocchio_system::ID),
     ];
 
     invoke(
@@ -96,7 +174,7 @@ fn create_config() {
             data: &data,
             accounts: &accounts,
         },
-        &[&manager, &claim_config],
+        &[&manager, &claim_config, &system_program_account],
     );
 
     let config_data = unsafe { claim_config.borrow_data_unchecked() };
@@ -113,6 +191,19 @@ fn create_config() {
     assert_eq!(config.min_amount_to_claim, 0);
     assert_eq!(config.token_id, token_id);
     assert_eq!(config.bump, config_id_bump);
+    assert_eq!(unsafe { claim_config.owner() }, &program_id);
+
+    // the manager only pays what the prefunded lamports lack
+    let minimum_balance = Rent::get().unwrap().minimum_balance(config_data.len());
+    assert_eq!(
+        config_data.len(),
+        ClaimConfig::DISCRIMINATOR.len() + ClaimConfig::DATA_SIZE
+    );
+    assert_eq!(claim_config.lamports(), minimum_balance);
+    assert_eq!(
+        manager.lamports(),
+        MANAGER_LAMPORTS - (minimum_balance - prefunded_lamports)
+    );
 }
 
 #[test]
@@ -121,7 +212,8 @@ fn update_config() {
     let token_id = [150; 32];
     let manager_id = [50; 32];
 
-    let (config_id, config_id_bump) = pubkey::find_program_address(&[b"todo"], &program_id);
+    let (config_id, config_id_bump) =
+        pubkey::find_program_address(&[b"claim_config", &manager_id, &token_id], &program_id);
 
     let (manager, claim_config) = MOCK_RUNTIME.with_borrow_mut(|rt| {
         rt.register_program_account(
@@ -229,3 +321,467 @@ fn update_config() {
     assert_eq!(config.token_id, token_id);
     assert_eq!(config.bump, config_id_bump);
 }
+
+/// A contract whose handlers misbehave, to test what the dispatcher does
+/// about it.
+struct TestClaim;
+
+impl ClaimContract for TestClaim {
+    fn create_claim(
+        _program_id: &Pubkey,
+        _accounts: &mut CreateClaimAccounts,
+        _amount: u64,
+        _claim_authority: [u8; 32],
+        _claim_bump: u8,
+    ) -> Result<(), ProgramError> {
+        unreachable!()
+    }
+
+    /// Changes the claim, then fails.
+    fn update_claim(
+        _program_id: &Pubkey,
+        accounts: &mut UpdateClaimAccounts,
+        amount: u64,
+    ) -> Result<u64, ProgramError> {
+        accounts.claim.as_ref_mut().amount_acquired = amount;
+        Err(ProgramError::Custom(42))
+    }
+
+    fn claim(_program_id: &Pubkey, _accounts: &mut ClaimAccounts) -> Result<(), ProgramError> {
+        unreachable!()
+    }
+
+    fn create_config(
+        _program_id: &Pubkey,
+        _accounts: &mut CreateConfigAccounts,
+        _config_bump: u8,
+        _token_id: Pubkey,
+    ) -> Result<(), ProgramError> {
+        unreachable!()
+    }
+
+    /// Changes the manually applied config without applying it, failing for
+    /// amounts over 1000.
+    fn update_config(
+        _program_id: &Pubkey,
+        accounts: &mut UpdateConfigAccounts,
+        amount: u64,
+    ) -> Result<(), ProgramError> {
+        accounts.claim_config.as_ref_mut().min_amount_to_claim = amount;
+        if amount > 1000 {
+            return Err(ProgramError::Custom(42));
+        }
+        Ok(())
+    }
+
+    /// Takes all the withdrawable lamports of every claim and `amount` more.
+    fn update_claims(
+        _program_id: &Pubkey,
+        accounts: &mut UpdateClaimsAccounts,
+        amount: u64,
+    ) -> Result<(), ProgramError> {
+        for claim in &mut accounts.remaining {
+            let mut claim = claim?;
+            let withdrawn = claim.withdrawable_lamports()? + amount;
+            claim.set_lamports(claim.lamports() - withdrawn);
+        }
+        Ok(())
+    }
+
+    fn redeem_voucher(
+        _program_id: &Pubkey,
+        _accounts: &mut RedeemVoucherAccounts,
+        _claim_key: Pubkey,
+        _redeemed: u64,
+        _amount_to_add: u64,
+    ) -> Result<(), ProgramError> {
+        unreachable!()
+    }
+}
+
+const PROGRAM_ID: Pubkey = [250; 32];
+const TOKEN_ID: Pubkey = [150; 32];
+const MANAGER_ID: Pubkey = [50; 32];
+const USER_ID: Pubkey = [70; 32];
+
+/// Registers the claim program dispatching to `C`, and the system program.
+fn register_programs<C: ClaimContract>() {
+    MOCK_RUNTIME.with_borrow_mut(|rt| {
+        rt.register_program_account(
+            "my_claim",
+            MockProgramAccount::new_program(
+                false,
+                false,
+                PROGRAM_ID,
+                pinocchio_system::ID,
+                0,
+                ClaimDispatcher::<C>::dispatch,
+            ),
+        );
+        rt.register_program_account(
+            "system_program",
+            MockProgramAccount::new_program(
+                false,
+                false,
+                pinocchio_system::ID,
+                pinocchio_system::ID,
+                0,
+                system_program,
+            ),
+        );
+    });
+}
+
+/// Registers `account` at `key`, returning it.
+fn register_account(name: &str, key: Pubkey, account: MockAccount) -> AccountInfo {
+    MOCK_RUNTIME.with_borrow_mut(|rt| {
+        rt.register_data_account(name, account);
+        rt.get_data_account(&key).unwrap()
+    })
+}
+
+/// Registers the signing manager wallet, holding `lamports`.
+fn register_manager(lamports: u64) -> AccountInfo {
+    register_account(
+        "manager_account",
+        MANAGER_ID,
+        MockAccount::new_data_account(
+            true,
+            true,
+            MANAGER_ID,
+            pinocchio_system::ID,
+            lamports,
+            vec![],
+        ),
+    )
+}
+
+/// Registers the config of the manager and token, holding `lamports`.
+fn register_config(lamports: u64) -> AccountInfo {
+    let (config_id, config_bump) =
+        pubkey::find_program_address(&ClaimConfig::seeds(&MANAGER_ID, &TOKEN_ID), &PROGRAM_ID);
+    let data = AccountData::new(ClaimConfig {
+        manager_authority: MANAGER_ID,
+        min_amount_to_claim: 0,
+        token_id: TOKEN_ID,
+        bump: config_bump,
+    })
+    .to_bytes()
+    .unwrap();
+    register_account(
+        "claim_config",
+        config_id,
+        MockAccount::new_data_account(false, true, config_id, PROGRAM_ID, lamports, data),
+    )
+}
+
+/// Registers the claim of `user_id`, holding `lamports`.
+fn register_claim(user_id: Pubkey, amount_acquired: u64, lamports: u64) -> AccountInfo {
+    let (claim_id, claim_bump) =
+        pubkey::find_program_address(&Claim::seeds(&MANAGER_ID, &TOKEN_ID, &user_id), &PROGRAM_ID);
+    let data = AccountData::new(Claim {
+        amount_acquired,
+        claim_authority: user_id,
+        manager_authority: MANAGER_ID,
+        redeemed: 0,
+        bump: claim_bump,
+    })
+    .to_bytes()
+    .unwrap();
+    register_account(
+        "claim",
+        claim_id,
+        MockAccount::new_data_account(false, true, claim_id, PROGRAM_ID, lamports, data),
+    )
+}
+
+/// Deserializes the data of a program account.
+fn account_data<T>(account: &AccountInfo) -> T
+where
+    T: BorshDeserialize + AccountDataConfig<4>,
+{
+    let data = unsafe { account.borrow_data_unchecked() };
+    let (discriminator, data) = data.split_at(T::DISCRIMINATOR.len());
+    assert_eq!(discriminator, T::DISCRIMINATOR);
+    T::try_from_slice(data).expect("failed to deserialize account")
+}
+
+/// Updating a claim applies the new amount and returns it.
+#[test]
+fn update_claim() {
+    register_programs::<MyClaim>();
+    let manager = register_manager(0);
+    let claim_config = register_config(0);
+    let claim = register_claim(USER_ID, 10, 0);
+
+    let mut data = UPDATE_CLAIM.to_vec();
+    data.extend(500u64.to_le_bytes());
+    let result = ClaimDispatcher::<MyClaim>::dispatch(
+        &PROGRAM_ID,
+        &[manager, claim_config, claim.clone()],
+        &data,
+    );
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(get_return_data::<u64>(&PROGRAM_ID), Ok(510));
+    assert_eq!(account_data::<Claim>(&claim).amount_acquired, 510);
+}
+
+/// Return data no program set is told apart from corrupt return data.
+#[test]
+fn missing_return_data() {
+    assert_eq!(
+        get_return_data::<u64>(&PROGRAM_ID),
+        Err(ErrorCode::MissingReturnData.into())
+    );
+}
+
+/// A handler that fails after changing an account leaves it unchanged.
+#[test]
+fn failed_update_claim_is_not_applied() {
+    register_programs::<TestClaim>();
+    let manager = register_manager(0);
+    let claim_config = register_config(0);
+    let claim = register_claim(USER_ID, 10, 0);
+
+    let mut data = UPDATE_CLAIM.to_vec();
+    data.extend(500u64.to_le_bytes());
+    let result = ClaimDispatcher::<TestClaim>::dispatch(
+        &PROGRAM_ID,
+        &[manager, claim_config, claim.clone()],
+        &data,
+    );
+
+    assert_eq!(result, Err(ProgramError::Custom(42)));
+    assert_eq!(account_data::<Claim>(&claim).amount_acquired, 10);
+}
+
+/// A `manual_apply` account changed without being applied fails a debug
+/// assertion instead of being silently dropped.
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "account claim_config changed but never applied")]
+fn unapplied_manual_config_panics() {
+    register_programs::<TestClaim>();
+    let manager = register_manager(0);
+    let claim_config = register_config(0);
+
+    let mut data = UPDATE_CONFIG.to_vec();
+    data.extend(100u64.to_le_bytes());
+    let _ = ClaimDispatcher::<TestClaim>::dispatch(&PROGRAM_ID, &[manager, claim_config], &data);
+}
+
+/// A failing handler may leave its `manual_apply` accounts changed, its error
+/// is returned and the changes are dropped.
+#[test]
+fn failed_manual_config_is_dropped() {
+    register_programs::<TestClaim>();
+    let manager = register_manager(0);
+    let claim_config = register_config(0);
+
+    let mut data = UPDATE_CONFIG.to_vec();
+    data.extend(5000u64.to_le_bytes());
+    let result = ClaimDispatcher::<TestClaim>::dispatch(
+        &PROGRAM_ID,
+        &[manager, claim_config.clone()],
+        &data,
+    );
+
+    assert_eq!(result, Err(ProgramError::Custom(42)));
+    assert_eq!(
+        account_data::<ClaimConfig>(&claim_config).min_amount_to_claim,
+        0
+    );
+}
+
+/// A claim passed twice as a remaining account is rejected instead of being
+/// updated twice.
+#[test]
+fn update_duplicate_claims() {
+    register_programs::<MyClaim>();
+    let manager = register_manager(0);
+    let claim_config = register_config(0);
+    let claim = register_claim(USER_ID, 10, 0);
+
+    let mut data = UPDATE_CLAIMS.to_vec();
+    data.extend(5u64.to_le_bytes());
+    let result = ClaimDispatcher::<MyClaim>::dispatch(
+        &PROGRAM_ID,
+        &[manager, claim_config, claim.clone(), claim],
+        &data,
+    );
+
+    assert_eq!(result, Err(ErrorCode::DuplicateAccount.into()));
+}
+
+/// Takes the withdrawable lamports of a claim and `extra` more, returning the
+/// result of the instruction and the lamports left in the claim.
+fn drain_claim(extra: u64) -> (ProgramResult, u64) {
+    register_programs::<TestClaim>();
+    let manager = register_manager(0);
+    let claim_config = register_config(0);
+    let claim = register_claim(USER_ID, 10, 1_000_000_000);
+
+    let mut data = UPDATE_CLAIMS.to_vec();
+    data.extend(extra.to_le_bytes());
+    let result = ClaimDispatcher::<TestClaim>::dispatch(
+        &PROGRAM_ID,
+        &[manager, claim_config, claim.clone()],
+        &data,
+    );
+    (result, claim.lamports())
+}
+
+/// A remaining claim can be drained down to its rent exempt minimum.
+#[test]
+fn drain_claim_to_rent_exempt_minimum() {
+    let (result, lamports) = drain_claim(0);
+
+    let data_len = Claim::DISCRIMINATOR.len() + Claim::DATA_SIZE;
+    assert_eq!(result, Ok(()));
+    assert_eq!(lamports, Rent::get().unwrap().minimum_balance(data_len));
+}
+
+/// A remaining claim drained below its rent exempt minimum fails the
+/// instruction.
+#[test]
+fn drain_claim_below_rent_exempt_minimum() {
+    let (result, _) = drain_claim(1);
+
+    assert_eq!(result, Err(ProgramError::AccountNotRentExempt));
+}
+
+/// Loads the claim as a writable program account.
+fn writable_claim(claim: &AccountInfo) -> AccountWritable<'_, AccountData<4, Claim>> {
+    AccountBuilder::new(claim)
+        .owner(&PROGRAM_ID)
+        .and_then(|claim| claim.set_payload().mutable())
+        .and_then(|claim| claim.build())
+        .unwrap()
+}
+
+/// Loads a wallet as a writable signer.
+fn signed_wallet(wallet: &AccountInfo) -> AccountWritableSigned<'_, Empty> {
+    AccountBuilder::new(wallet)
+        .signed()
+        .and_then(|wallet| wallet.mutable())
+        .and_then(|wallet| wallet.build())
+        .unwrap()
+}
+
+/// The rent exempt minimum of a claim.
+fn claim_minimum_balance() -> u64 {
+    Rent::get()
+        .unwrap()
+        .minimum_balance(Claim::DISCRIMINATOR.len() + Claim::DATA_SIZE)
+}
+
+/// A program account is debited directly, down to its rent exempt minimum.
+#[test]
+fn transfer_from_program_account() {
+    register_programs::<MyClaim>();
+    let manager = register_manager(0);
+    let claim = register_claim(USER_ID, 0, claim_minimum_balance() + 1_000);
+
+    let result = transfer(
+        &mut writable_claim(&claim),
+        &mut signed_wallet(&manager),
+        1_000,
+    );
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(claim.lamports(), claim_minimum_balance());
+    assert_eq!(manager.lamports(), 1_000);
+}
+
+/// A program account can't be debited below its rent exempt minimum.
+#[test]
+fn transfer_from_program_account_below_rent_exempt_minimum() {
+    register_programs::<MyClaim>();
+    let manager = register_manager(0);
+    let claim = register_claim(USER_ID, 0, claim_minimum_balance() + 1_000);
+
+    let result = transfer(
+        &mut writable_claim(&claim),
+        &mut signed_wallet(&manager),
+        1_001,
+    );
+
+    assert_eq!(result, Err(ProgramError::AccountNotRentExempt));
+    assert_eq!(claim.lamports(), claim_minimum_balance() + 1_000);
+}
+
+/// A signing system account is debited through the system program, and may
+/// be emptied.
+#[test]
+fn transfer_from_system_account() {
+    register_programs::<MyClaim>();
+    let manager = register_manager(1_000);
+    let claim = register_claim(USER_ID, 0, 0);
+
+    let result = transfer(
+        &mut signed_wallet(&manager),
+        &mut writable_claim(&claim),
+        1_000,
+    );
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(manager.lamports(), 0);
+    assert_eq!(claim.lamports(), 1_000);
+}
+
+/// A system account can't be debited of more lamports than it holds.
+#[test]
+fn transfer_insufficient_funds() {
+    register_programs::<MyClaim>();
+    let manager = register_manager(1_000);
+    let claim = register_claim(USER_ID, 0, 0);
+
+    let result = transfer(
+        &mut signed_wallet(&manager),
+        &mut writable_claim(&claim),
+        1_001,
+    );
+
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+    assert_eq!(manager.lamports(), 1_000);
+}
+
+/// A system account can't be left holding less than the rent exempt minimum
+/// without being emptied.
+#[test]
+fn transfer_from_system_account_below_rent_exempt_minimum() {
+    register_programs::<MyClaim>();
+    let manager = register_manager(1_000);
+    let claim = register_claim(USER_ID, 0, 0);
+
+    let result = transfer(
+        &mut signed_wallet(&manager),
+        &mut writable_claim(&claim),
+        999,
+    );
+
+    assert_eq!(result, Err(ProgramError::AccountNotRentExempt));
+    assert_eq!(manager.lamports(), 1_000);
+}
+
+/// An account without data that isn't owned by the system program can't be
+/// debited through it.
+#[test]
+fn transfer_from_account_not_owned_by_system_program() {
+    register_programs::<MyClaim>();
+    let wallet = register_account(
+        "wallet",
+        USER_ID,
+        MockAccount::new_data_account(true, true, USER_ID, PROGRAM_ID, 1_000, vec![]),
+    );
+    let claim = register_claim(USER_ID, 0, 0);
+
+    let result = transfer(
+        &mut signed_wallet(&wallet),
+        &mut writable_claim(&claim),
+        1_000,
+    );
+
+    assert_eq!(result, Err(ProgramError::IllegalOwner));
+}
@@ -1,50 +1,74 @@
 use crate::counter_contract::{
-    CloseAccounts, Count, CounterContract, CounterDispatcher, IncrementAccounts, InitalizeAccounts,
+    CloseAccounts, Count, CounterContract, IncrementAccounts, InitalizeAccounts,
+    InitalizeKeypairAccounts, KeypairCount,
 };
-use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
 use pinocchio_log::log;
-use sol_ez::Contract;
-
-pub const FN: fn(&Pubkey, &[AccountInfo], &[u8]) -> Result<(), ProgramError> =
-    CounterDispatcher::<MyCounter>::dispatch;
 
 pub struct MyCounter;
 
 impl CounterContract for MyCounter {
     fn initalize(
         owner: &Pubkey,
-        mut accounts: InitalizeAccounts,
+        accounts: &mut InitalizeAccounts,
         amount: u8,
+        bump: u8,
     ) -> Result<(), ProgramError> {
+        let user = accounts.user.key();
         let account = Count {
-            authority: *accounts.user.key(),
+            authority: *user,
             value: amount,
-            bump: 0,
+            bump,
         };
-        let counter = accounts.count.init(account, 0, &mut accounts.user, owner)?;
+        let seeds = Count::seeds(user);
+        let payer = match accounts.payer.as_mut() {
+            Some(payer) => payer,
+            None => &mut accounts.user,
+        };
+        let counter = accounts.count.init(account, &seeds, bump, payer, owner)?;
         log!("Counter initialized with value: {}", counter.as_ref().value);
         Ok(())
     }
 
-    fn increment(_owner: &Pubkey, mut accounts: IncrementAccounts) -> Result<(), ProgramError> {
-        validate(accounts.user.key(), accounts.count.as_ref())?;
-        accounts.count.as_ref_mut().value += 1;
-        let counter = accounts.count.apply()?;
-        log!("Counter incremented to: {}", counter.as_ref().value);
+    fn initalize_keypair(
+        owner: &Pubkey,
+        accounts: &mut InitalizeKeypairAccounts,
+        amount: u8,
+    ) -> Result<(), ProgramError> {
+        let account = KeypairCount {
+            authority: *accounts.user.key(),
+            value: amount,
+        };
+        let counter = accounts
+            .count
+            .init_keypair(account, &mut accounts.user, owner)?;
+        log!("Counter initialized with value: {}", counter.as_ref().value);
         Ok(())
     }
 
-    fn close(_owner: &Pubkey, mut accounts: CloseAccounts) -> Result<(), ProgramError> {
-        validate(accounts.user.key(), accounts.count.as_ref())?;
-        accounts.count.close(&mut accounts.user)?;
-        log!("Counter closed");
+    fn increment(
+        owner: &Pubkey,
+        accounts: &mut IncrementAccounts,
+        bump: u8,
+    ) -> Result<(), ProgramError> {
+        let user = accounts.user.key();
+        let count =
+            accounts
+                .count
+                .or_init(&Count::seeds(user), bump, &mut accounts.user, owner, || {
+                    Count {
+                        authority: *user,
+                        value: 0,
+                        bump,
+                    }
+                })?;
+        count.as_ref_mut().value += 1;
+        log!("Counter incremented to: {}", count.as_ref().value);
         Ok(())
     }
-}
 
-fn validate(user_key: &Pubkey, count: &Count) -> Result<(), ProgramError> {
-    if count.authority != *user_key {
-        return Err(ProgramError::IllegalOwner);
+    fn close(_owner: &Pubkey, _accounts: &mut CloseAccounts) -> Result<(), ProgramError> {
+        log!("Counter closed");
+        Ok(())
     }
-    Ok(())
 }
@@ -2,6 +2,14 @@ use core::marker::PhantomData;
 use borsh::{BorshDeserialize, BorshSerialize};
 use sol_ez::{account::*, account_info::*, AccountData, AccountDataConfig, DataSize};
 use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+pub const ID: Pubkey = [
+    124u8, 52u8, 159u8, 161u8, 191u8, 59u8, 56u8, 187u8, 154u8, 92u8, 21u8, 184u8, 213u8,
+    107u8, 60u8, 245u8, 184u8, 29u8, 224u8, 164u8, 210u8, 72u8, 33u8, 204u8, 92u8, 117u8,
+    113u8, 17u8, 47u8, 73u8, 152u8, 145u8,
+];
+pub fn check_id(id: &Pubkey) -> bool {
+    id == &ID
+}
 #[derive(BorshSerialize, BorshDeserialize, AccountDataConfig)]
 #[account_data(hash(seed = "counter|account|count", size = 4usize))]
 pub struct Count {
@@ -9,88 +17,263 @@ pub struct Count {
     pub value: u8,
     pub bump: u8,
 }
+impl Count {
+    pub fn seeds(user: &Pubkey) -> [&[u8]; 2usize] {
+        [b"count".as_slice(), user.as_slice()]
+    }
+    pub fn signer<'a, 'info, P>(
+        account: &'a mut Account<'info, AccountData<4usize, Count>, P, Unsigned>,
+        user: &'a Pubkey,
+    ) -> sol_ez::cpi::PdaSigner<'a, 'info, AccountData<4usize, Count>, P, 2usize>
+    where
+        P: AccountRead,
+    {
+        let bump = account.as_ref().bump;
+        sol_ez::cpi::PdaSigner::new(account, Self::seeds(user), bump)
+    }
+}
+#[derive(BorshSerialize, BorshDeserialize, AccountDataConfig)]
+#[account_data(hash(seed = "counter|account|keypair_count", size = 4usize))]
+pub struct KeypairCount {
+    pub authority: [u8; 32],
+    pub value: u8,
+}
 pub struct InitalizeAccounts<'info> {
     pub user: AccountWritableSigned<'info, Empty>,
     pub count: Account<'info, PhantomData<AccountData<4usize, Count>>, Init, Unsigned>,
+    pub payer: Option<AccountWritableSigned<'info, Empty>>,
 }
 impl<'info> InitalizeAccounts<'info> {
     pub fn load(
+        program_id: &Pubkey,
         accounts: &'info [pinocchio::account_info::AccountInfo],
     ) -> Result<Self, ProgramError> {
-        Ok(Self {
-            user: AccountBuilder::new(
-                    accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .mutable()?
-                .signed()?
-                .build()?,
-            count: Account::new_init(
-                AccountInfo::new_init(
-                    accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )?,
-            ),
-        })
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(0usize, "user"), (1usize, "count"), (2usize, "payer")],
+            &[],
+        )?;
+        let user = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .mutable()?
+            .signed()?
+            .build()?;
+        let count = Account::new_init(
+            AccountInfo::new_init(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )?,
+        );
+        let payer = match accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?
+        {
+            account if account.key() == program_id => None,
+            account => Some(AccountBuilder::new(account).mutable()?.signed()?.build()?),
+        };
+        Ok(Self { user, count, payer })
+    }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
     }
 }
 pub struct IncrementAccounts<'info> {
     pub user: AccountWritableSigned<'info, Empty>,
-    pub count: AccountWritable<'info, AccountData<4usize, Count>>,
+    pub count: InitIfNeeded<'info, 4usize, Count>,
+    pub system_program: Program<'info, System>,
 }
 impl<'info> IncrementAccounts<'info> {
+    #[allow(clippy::nonminimal_bool)]
     pub fn load(
+        program_id: &Pubkey,
         accounts: &'info [pinocchio::account_info::AccountInfo],
     ) -> Result<Self, ProgramError> {
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(0usize, "user"), (1usize, "count")],
+            &[],
+        )?;
+        let user = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .mutable()?
+            .signed()?
+            .build()?;
+        let count = InitIfNeeded::<
+            4usize,
+            Count,
+        >::load(
+            accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            program_id,
+        )?;
+        let system_program = Program::new(
+            accounts.get(2usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        if let InitIfNeeded::Initialized(count) = &count {
+            if !(count.as_ref().authority == *user.key()) {
+                return Err(sol_ez::error::ErrorCode::Constraint.into());
+            }
+        }
         Ok(Self {
-            user: AccountBuilder::new(
-                    accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .mutable()?
-                .signed()?
-                .build()?,
-            count: AccountBuilder::new(
-                    accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .set_payload()
-                .mutable()?
-                .build()?,
+            user,
+            count,
+            system_program,
         })
     }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        mut self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        if let InitIfNeeded::Initialized(count) = &mut self.count {
+            count.apply()?;
+        }
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
+    }
 }
 pub struct CloseAccounts<'info> {
     pub user: AccountWritableSigned<'info, Empty>,
     pub count: AccountWritable<'info, AccountData<4usize, Count>>,
 }
 impl<'info> CloseAccounts<'info> {
+    #[allow(clippy::nonminimal_bool)]
     pub fn load(
+        program_id: &Pubkey,
         accounts: &'info [pinocchio::account_info::AccountInfo],
     ) -> Result<Self, ProgramError> {
-        Ok(Self {
-            user: AccountBuilder::new(
-                    accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .mutable()?
-                .signed()?
-                .build()?,
-            count: AccountBuilder::new(
-                    accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
-                )
-                .set_payload()
-                .mutable()?
-                .build()?,
-        })
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(0usize, "user"), (1usize, "count")],
+            &[],
+        )?;
+        let user = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .mutable()?
+            .signed()?
+            .build()?;
+        let count = AccountBuilder::new(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .owner(program_id)?
+            .set_payload::<4usize, Count>()
+            .mutable()?
+            .build()?;
+        sol_ez::account::check_address(
+            count.key(),
+            &[b"count".as_slice(), user.key().as_slice()],
+            count.as_ref().bump,
+            program_id,
+        )?;
+        if !(count.as_ref().authority == *user.key()) {
+            return Err(sol_ez::error::ErrorCode::Constraint.into());
+        }
+        Ok(Self { user, count })
+    }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        drop(self);
+        sol_ez::account::close_account::<
+            4usize,
+        >(
+            accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
+    }
+}
+pub struct InitalizeKeypairAccounts<'info> {
+    pub user: AccountWritableSigned<'info, Empty>,
+    pub count: Account<
+        'info,
+        PhantomData<AccountData<4usize, KeypairCount>>,
+        Init,
+        Signed,
+    >,
+}
+impl<'info> InitalizeKeypairAccounts<'info> {
+    pub fn load(
+        program_id: &Pubkey,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<Self, ProgramError> {
+        sol_ez::account::check_unique(
+            program_id,
+            accounts,
+            &[(0usize, "user"), (1usize, "count")],
+            &[],
+        )?;
+        let user = AccountBuilder::new(
+                accounts.get(0usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )
+            .mutable()?
+            .signed()?
+            .build()?;
+        let count = Account::new_init(
+            AccountInfo::new_init_signed(
+                accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            )?,
+        );
+        Ok(Self { user, count })
+    }
+    /// Applies the changed accounts, then checks the written accounts
+    /// are rent exempt and closes the closed accounts, once the
+    /// handler succeeded.
+    pub fn exit(
+        self,
+        accounts: &'info [pinocchio::account_info::AccountInfo],
+    ) -> Result<(), ProgramError> {
+        drop(self);
+        sol_ez::account::check_rent_exempt(
+            accounts.get(1usize).ok_or(ProgramError::NotEnoughAccountKeys)?,
+        )?;
+        Ok(())
     }
 }
 pub trait CounterContract {
+    /// Accounts created with `init` or `init_keypair` are only written by their `apply`.
     fn initalize(
         program_id: &Pubkey,
-        accounts: InitalizeAccounts,
+        accounts: &mut InitalizeAccounts,
         amount: u8,
+        bump: u8,
     ) -> Result<(), ProgramError>;
     fn increment(
         program_id: &Pubkey,
-        accounts: IncrementAccounts,
+        accounts: &mut IncrementAccounts,
+        bump: u8,
+    ) -> Result<(), ProgramError>;
+    fn close(
+        program_id: &Pubkey,
+        accounts: &mut CloseAccounts,
+    ) -> Result<(), ProgramError>;
+    /// Accounts created with `init` or `init_keypair` are only written by their `apply`.
+    fn initalize_keypair(
+        program_id: &Pubkey,
+        accounts: &mut InitalizeKeypairAccounts,
+        amount: u8,
     ) -> Result<(), ProgramError>;
-    fn close(program_id: &Pubkey, accounts: CloseAccounts) -> Result<(), ProgramError>;
 }
 pub struct CounterDispatcher<T> {
     inner: PhantomData<T>,
@@ -98,6 +281,7 @@ pub struct CounterDispatcher<T> {
 pub const INITALIZE: [u8; 4usize] = [165u8, 109u8, 64u8, 236u8];
 pub const INCREMENT: [u8; 4usize] = [139u8, 113u8, 235u8, 106u8];
 pub const CLOSE: [u8; 4usize] = [9u8, 199u8, 35u8, 185u8];
+pub const INITALIZE_KEYPAIR: [u8; 4usize] = [66u8, 214u8, 94u8, 184u8];
 impl<T> sol_ez::Contract for CounterDispatcher<T>
 where
     T: CounterContract,
@@ -108,21 +292,182 @@ where
         payload: &[u8],
     ) -> Result<(), ProgramError> {
         let ix_data = sol_ez::InstructionData::new(payload)?;
-        match ix_data.ix {
-            &INITALIZE => {
-                let accounts = InitalizeAccounts::load(accounts)?;
+        match *ix_data.ix {
+            INITALIZE => {
+                let mut ix_accounts = InitalizeAccounts::load(program_id, accounts)?;
+                let (amount, bump) = ix_data.deserialize_data()?;
+                T::initalize(program_id, &mut ix_accounts, amount, bump)?;
+                ix_accounts.exit(accounts)
+            }
+            INCREMENT => {
+                let mut ix_accounts = IncrementAccounts::load(program_id, accounts)?;
+                let bump = ix_data.deserialize_data()?;
+                T::increment(program_id, &mut ix_accounts, bump)?;
+                ix_accounts.exit(accounts)
+            }
+            CLOSE => {
+                let mut ix_accounts = CloseAccounts::load(program_id, accounts)?;
+                T::close(program_id, &mut ix_accounts)?;
+                ix_accounts.exit(accounts)
+            }
+            INITALIZE_KEYPAIR => {
+                let mut ix_accounts = InitalizeKeypairAccounts::load(
+                    program_id,
+                    accounts,
+                )?;
                 let amount = ix_data.deserialize_data()?;
-                T::initalize(program_id, accounts, amount)
+                T::initalize_keypair(program_id, &mut ix_accounts, amount)?;
+                ix_accounts.exit(accounts)
             }
-            &INCREMENT => {
-                let accounts = IncrementAccounts::load(accounts)?;
-                T::increment(program_id, accounts)
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+    fn dispatch_lazy(
+        mut context: pinocchio::entrypoint::InstructionContext,
+    ) -> Result<(), ProgramError> {
+        let (program_id, payload) = sol_ez::entrypoint::peek_instruction(&context)?;
+        let ix_data = sol_ez::InstructionData::new(payload)?;
+        match *ix_data.ix {
+            INITALIZE => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 3usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = InitalizeAccounts::load(program_id, accounts)?;
+                let (amount, bump) = ix_data.deserialize_data()?;
+                T::initalize(program_id, &mut ix_accounts, amount, bump)?;
+                ix_accounts.exit(accounts)
+            }
+            INCREMENT => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 3usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = IncrementAccounts::load(program_id, accounts)?;
+                let bump = ix_data.deserialize_data()?;
+                T::increment(program_id, &mut ix_accounts, bump)?;
+                ix_accounts.exit(accounts)
+            }
+            CLOSE => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 2usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = CloseAccounts::load(program_id, accounts)?;
+                T::close(program_id, &mut ix_accounts)?;
+                ix_accounts.exit(accounts)
             }
-            &CLOSE => {
-                let accounts = CloseAccounts::load(accounts)?;
-                T::close(program_id, accounts)
+            INITALIZE_KEYPAIR => {
+                let mut parsed = [const {
+                    core::mem::MaybeUninit::<
+                        pinocchio::account_info::AccountInfo,
+                    >::uninit()
+                }; 2usize];
+                let accounts = sol_ez::entrypoint::parse_accounts(
+                    &mut context,
+                    &mut parsed,
+                )?;
+                let mut ix_accounts = InitalizeKeypairAccounts::load(
+                    program_id,
+                    accounts,
+                )?;
+                let amount = ix_data.deserialize_data()?;
+                T::initalize_keypair(program_id, &mut ix_accounts, amount)?;
+                ix_accounts.exit(accounts)
             }
             _ => Err(ProgramError::InvalidInstructionData),
         }
     }
 }
+pub mod cpi {
+    use super::*;
+    use sol_ez::cpi::{CpiAccount, CpiMeta, CpiSigner, CpiWritable};
+    #[allow(clippy::too_many_arguments)]
+    pub fn initalize<'info>(
+        user: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        count: &mut impl CpiWritable<'info>,
+        payer: Option<&mut (impl CpiWritable<'info> + CpiSigner<'info>)>,
+        program: &impl CpiAccount<'info>,
+        amount: u8,
+        bump: u8,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 6usize];
+        data[..INITALIZE.len()].copy_from_slice(&INITALIZE);
+        sol_ez::cpi::serialize_args(&mut data[INITALIZE.len()..], &(amount, bump))?;
+        sol_ez::cpi::invoke(
+            &ID,
+            [
+                CpiMeta::writable_signer(user),
+                CpiMeta::writable(count),
+                match payer {
+                    Some(payer) => CpiMeta::writable_signer(payer),
+                    None => CpiMeta::placeholder(&ID, program)?,
+                },
+            ],
+            &data,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn increment<'info>(
+        user: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        count: &mut impl CpiWritable<'info>,
+        system_program: &mut Program<'info, System>,
+        bump: u8,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 5usize];
+        data[..INCREMENT.len()].copy_from_slice(&INCREMENT);
+        sol_ez::cpi::serialize_args(&mut data[INCREMENT.len()..], &(bump,))?;
+        sol_ez::cpi::invoke(
+            &ID,
+            [
+                CpiMeta::writable_signer(user),
+                CpiMeta::writable(count),
+                CpiMeta::readonly(system_program),
+            ],
+            &data,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn close<'info>(
+        user: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        count: &mut impl CpiWritable<'info>,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 4usize];
+        data[..CLOSE.len()].copy_from_slice(&CLOSE);
+        sol_ez::cpi::invoke(
+            &ID,
+            [CpiMeta::writable_signer(user), CpiMeta::writable(count)],
+            &data,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn initalize_keypair<'info>(
+        user: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        count: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        amount: u8,
+    ) -> Result<(), ProgramError> {
+        let mut data = [0; 5usize];
+        data[..INITALIZE_KEYPAIR.len()].copy_from_slice(&INITALIZE_KEYPAIR);
+        sol_ez::cpi::serialize_args(&mut data[INITALIZE_KEYPAIR.len()..], &(amount,))?;
+        sol_ez::cpi::invoke(
+            &ID,
+            [CpiMeta::writable_signer(user), CpiMeta::writable_signer(count)],
+            &data,
+        )
+    }
+}
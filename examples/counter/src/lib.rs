@@ -1,14 +1,8 @@
 #![no_std]
 
-#[cfg(not(feature = "bpf"))]
-pub use crate::{counter::MyCounter, counter::FN, counter_contract::*};
+pub use crate::{counter::MyCounter, counter_contract::*};
 
-#[cfg(feature = "bpf")]
-mod entrypoint {
-    use crate::counter::FN;
-    use pinocchio::entrypoint;
-    entrypoint!(FN);
-}
+sol_ez::program!(MyCounter => CounterDispatcher);
 
 // generated code
 mod counter_contract;
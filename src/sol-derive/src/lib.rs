@@ -2,7 +2,9 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
-use sol_gen_common::discriminator::{DiscriminatorGen, HashDiscriminatorGen};
+use sol_gen_common::discriminator::{
+    is_closed_discriminator, DiscriminatorGen, HashDiscriminatorGen,
+};
 use syn::DeriveInput;
 
 #[derive(deluxe::ExtractAttributes)]
@@ -25,6 +27,12 @@ fn derive_account_data_2(
                 size as usize,
             ),
         };
+        if is_closed_discriminator(&bytes) {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "discriminator is reserved for closed accounts",
+            ));
+        }
         (quote! { [#( #bytes ),*] }, size)
     };
 
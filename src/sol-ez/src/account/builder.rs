@@ -1,16 +1,22 @@
 use core::marker::PhantomData;
 
 use borsh::BorshDeserialize;
-use pinocchio::program_error::ProgramError;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
 
-use crate::account_info::{AccountInfo, AccountRead, Empty, Immutable, Mutable, Signed, Unsigned};
+use crate::{
+    account_info::{AccountInfo, AccountRead, Empty, Immutable, Mutable, Signed, Unsigned},
+    token::{self, TokenData},
+};
 
 use super::{pda::AccountDataConfig, Account, AccountData};
 
 pub struct Set<T>(PhantomData<T>);
 
+pub struct SetToken<T>(PhantomData<T>);
+
 pub struct AccountBuilder<'info, T, M, S> {
     account_info: &'info pinocchio::account_info::AccountInfo,
+    locked: bool,
     payload: PhantomData<T>,
     mutable: PhantomData<M>,
     signed: PhantomData<S>,
@@ -20,6 +26,7 @@ impl<'info> AccountBuilder<'info, Empty, Immutable, Unsigned> {
     pub fn new(account_info: &'info pinocchio::account_info::AccountInfo) -> Self {
         Self {
             account_info,
+            locked: true,
             payload: PhantomData,
             mutable: PhantomData,
             signed: PhantomData,
@@ -31,7 +38,7 @@ impl<'info, M, S> AccountBuilder<'info, Empty, M, S> {
     pub fn build(self) -> Result<Account<'info, Empty, M, S>, ProgramError> {
         Ok(Account {
             inner: Empty,
-            account_info: AccountInfo::new(self.account_info)?,
+            account_info: self.account_info()?,
         })
     }
 }
@@ -44,11 +51,38 @@ impl<'info, T, M, S> AccountBuilder<'info, Set<T>, M, S> {
         T: AccountDataConfig<DISCRIMINATOR_SIZE> + BorshDeserialize,
         M: AccountRead,
     {
-        Account::new(AccountInfo::new(self.account_info)?)
+        Account::new(self.account_info()?)
+    }
+}
+
+impl<'info, T, M, S> AccountBuilder<'info, SetToken<T>, M, S> {
+    pub fn build(self) -> Result<Account<'info, T, M, S>, ProgramError>
+    where
+        T: TokenData,
+        M: AccountRead,
+    {
+        let account_info = self.account_info()?;
+        Ok(Account {
+            inner: token::unpack_owned(account_info.owner(), account_info.data())?,
+            account_info,
+        })
     }
 }
 
 impl<'info, M, S> AccountBuilder<'info, Empty, M, S> {
+    pub fn token_payload<T>(self) -> AccountBuilder<'info, SetToken<T>, M, S>
+    where
+        T: TokenData,
+    {
+        AccountBuilder {
+            account_info: self.account_info,
+            locked: self.locked,
+            payload: PhantomData,
+            mutable: PhantomData,
+            signed: PhantomData,
+        }
+    }
+
     pub fn set_payload<const DISCRIMINATOR_SIZE: usize, T>(
         self,
     ) -> AccountBuilder<'info, Set<T>, M, S>
@@ -57,6 +91,7 @@ impl<'info, M, S> AccountBuilder<'info, Empty, M, S> {
     {
         AccountBuilder {
             account_info: self.account_info,
+            locked: self.locked,
             payload: PhantomData,
             mutable: PhantomData,
             signed: PhantomData,
@@ -64,13 +99,41 @@ impl<'info, M, S> AccountBuilder<'info, Empty, M, S> {
     }
 }
 
+impl<'info, T, M, S> AccountBuilder<'info, T, M, S> {
+    fn account_info(&self) -> Result<AccountInfo<'info, M, S>, ProgramError> {
+        if self.locked {
+            AccountInfo::new(self.account_info)
+        } else {
+            Ok(AccountInfo::new_unlocked(self.account_info))
+        }
+    }
+
+    pub fn owner(self, owner: &Pubkey) -> Result<Self, ProgramError> {
+        if !self.account_info.is_owned_by(owner) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        Ok(self)
+    }
+}
+
 impl<'info, T, S> AccountBuilder<'info, T, Immutable, S> {
+    /// Loads the account without locking it, so it may also be passed as
+    /// another account of the instruction.
+    pub fn allow_duplicate(self) -> Self {
+        Self {
+            locked: false,
+            ..self
+        }
+    }
+
     pub fn mutable(self) -> Result<AccountBuilder<'info, T, Mutable, S>, ProgramError> {
         if !self.account_info.is_writable() {
             return Err(ProgramError::Immutable);
         }
+        // mutable accounts are always locked
         Ok(AccountBuilder {
             account_info: self.account_info,
+            locked: true,
             payload: PhantomData,
             mutable: PhantomData,
             signed: PhantomData,
@@ -85,6 +148,7 @@ impl<'info, T, M> AccountBuilder<'info, T, M, Unsigned> {
         }
         Ok(AccountBuilder {
             account_info: self.account_info,
+            locked: self.locked,
             payload: PhantomData,
             mutable: PhantomData,
             signed: PhantomData,
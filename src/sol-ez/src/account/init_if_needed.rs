@@ -0,0 +1,96 @@
+use core::marker::PhantomData;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::account_info::{AccountInfo, Init, Mutable, Signed, Unsigned};
+
+use super::{pda::check_address, Account, AccountBuilder, AccountData, AccountDataConfig};
+
+/// An account that is created by the instruction on first use, and loaded on
+/// every use after.
+///
+/// An account is only considered uninitialized when it holds no data and is
+/// still owned by the system program. Otherwise it is loaded like any other
+/// account, checking its owner and discriminator, so an existing account can
+/// never be initialized again.
+pub enum InitIfNeeded<'info, const DISCRIMINATOR_SIZE: usize, T> {
+    /// The account does not exist yet.
+    Uninitialized(Account<'info, PhantomData<AccountData<DISCRIMINATOR_SIZE, T>>, Init, Unsigned>),
+    /// The account exists and was loaded.
+    Initialized(Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, Unsigned>),
+}
+
+impl<'info, const DISCRIMINATOR_SIZE: usize, T> InitIfNeeded<'info, DISCRIMINATOR_SIZE, T>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE>,
+{
+    pub fn load(
+        account_info: &'info pinocchio::account_info::AccountInfo,
+        owner: &Pubkey,
+    ) -> Result<Self, ProgramError>
+    where
+        T: BorshDeserialize,
+    {
+        if account_info.data_is_empty() && account_info.is_owned_by(&pinocchio_system::ID) {
+            return Ok(InitIfNeeded::Uninitialized(Account::new_init(
+                AccountInfo::new_init(account_info)?,
+            )));
+        }
+        let account = AccountBuilder::new(account_info)
+            .owner(owner)?
+            .set_payload()
+            .mutable()?
+            .build()?;
+        Ok(InitIfNeeded::Initialized(account))
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        matches!(self, InitIfNeeded::Initialized(_))
+    }
+
+    pub fn key(&self) -> &'info Pubkey {
+        match self {
+            InitIfNeeded::Uninitialized(account) => account.key(),
+            InitIfNeeded::Initialized(account) => account.key(),
+        }
+    }
+
+    /// Returns the loaded account, or creates it at the program derived
+    /// address of `seeds` and `bump` with the data returned by `init`.
+    ///
+    /// A loaded account must be at that address too, so another account of
+    /// the same type can't be passed in its place. Fails with
+    /// [`ProgramError::InvalidSeeds`] otherwise.
+    ///
+    /// The account is kept initialized in place, so the dispatcher applies
+    /// changes made to it like to any other loaded account.
+    pub fn or_init<P>(
+        &mut self,
+        seeds: &[&[u8]],
+        bump: u8,
+        payer: &mut Account<'info, P, Mutable, Signed>,
+        owner: &Pubkey,
+        init: impl FnOnce() -> T,
+    ) -> Result<
+        &mut Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, Unsigned>,
+        ProgramError,
+    >
+    where
+        T: BorshSerialize,
+    {
+        match self {
+            InitIfNeeded::Uninitialized(account) => {
+                let account = account.create(init(), seeds, bump, payer, owner)?;
+                *self = InitIfNeeded::Initialized(account);
+            }
+            InitIfNeeded::Initialized(account) => {
+                check_address(account.key(), seeds, bump, owner)?;
+            }
+        }
+        match self {
+            InitIfNeeded::Initialized(account) => Ok(account),
+            InitIfNeeded::Uninitialized(_) => unreachable!("the account was created above"),
+        }
+    }
+}
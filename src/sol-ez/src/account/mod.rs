@@ -1,12 +1,28 @@
-pub use pda::{AccountData, AccountDataConfig};
-use pinocchio::pubkey::Pubkey;
+pub use pda::{
+    check_address, check_rent_exempt, close_account, AccountData, AccountDataConfig,
+    CLOSED_DISCRIMINATOR_BYTE,
+};
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, sysvars::Sysvar as _};
 
 use crate::account_info::{AccountInfo, AccountRead, Immutable, Mutable, Signed, Unsigned};
 
 pub use builder::AccountBuilder;
+pub use init_if_needed::InitIfNeeded;
+pub use program::{AssociatedToken, Program, ProgramId, System, Token, Token2022, TokenProgram};
+pub use remaining::RemainingAccounts;
+pub use sysvar::{
+    Clock, EpochSchedule, Instructions, IntrospectedAccountMeta, IntrospectedInstruction,
+    IntrospectedInstructions, Rent, Sysvar, SysvarId,
+};
+pub use unique::check_unique;
 
 mod builder;
+mod init_if_needed;
 mod pda;
+mod program;
+mod remaining;
+mod sysvar;
+mod unique;
 
 pub type AccountReadOnly<'info, T> = Account<'info, T, Immutable, Unsigned>;
 pub type AccountWritable<'info, T> = Account<'info, T, Mutable, Unsigned>;
@@ -45,7 +61,7 @@ pub struct Account<'info, T, P, S> {
 }
 
 impl<'info, T, P, S> Account<'info, T, P, S> {
-    pub fn key(&self) -> &Pubkey {
+    pub fn key(&self) -> &'info Pubkey {
         self.account_info.key()
     }
 
@@ -59,6 +75,17 @@ impl<'info, T, P, S> Account<'info, T, P, S> {
     {
         self.account_info.lamports()
     }
+
+    /// The lamports that can be taken from the account while leaving it rent
+    /// exempt.
+    pub fn withdrawable_lamports(&self) -> Result<u64, ProgramError>
+    where
+        P: AccountRead,
+    {
+        let minimum_balance =
+            pinocchio::sysvars::rent::Rent::get()?.minimum_balance(self.account_info.data().len());
+        Ok(self.account_info.lamports().saturating_sub(minimum_balance))
+    }
 }
 
 impl<'info, T, S> Account<'info, T, Mutable, S> {
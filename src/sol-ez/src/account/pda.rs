@@ -2,22 +2,28 @@ use core::{marker::PhantomData, ptr};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use pinocchio::{
-    instruction::{Seed, Signer},
+    instruction::Signer,
     program_error::ProgramError,
-    pubkey::{self, Pubkey},
+    pubkey::{self, Pubkey, MAX_SEEDS},
     sysvars::{rent::Rent, Sysvar},
 };
-use pinocchio_system::instructions::CreateAccount;
+use pinocchio_system::instructions::{Allocate, Assign, CreateAccount, Transfer};
 
 use crate::{
-    account_info::{
-        AccountInfo, AccountRead, AccountWrite, Immutable, Init, Mutable, Signed, Unsigned,
-    },
+    account_info::{AccountInfo, AccountRead, AccountWrite, Init, Mutable, Signed, Unsigned},
+    cpi,
+    error::ErrorCode,
     split_at_fixed_unchecked,
 };
 
 use super::Account;
 
+/// The byte every discriminator byte of a closed account is set to.
+///
+/// A discriminator made only of this byte is reserved, so a closed account
+/// can never be loaded as any account type.
+pub const CLOSED_DISCRIMINATOR_BYTE: u8 = u8::MAX;
+
 pub trait AccountDataConfig<const DISCRIMINATOR_SIZE: usize> {
     const DATA_SIZE: usize;
     const DISCRIMINATOR: [u8; DISCRIMINATOR_SIZE];
@@ -25,6 +31,13 @@ pub trait AccountDataConfig<const DISCRIMINATOR_SIZE: usize> {
 
 pub struct AccountData<const DISCRIMINATOR_SIZE: usize, T> {
     inner: T,
+    /// Whether the data was changed through [`Account::as_ref_mut`] since it
+    /// was last written to the account.
+    ///
+    /// Changed data is only written by [`Account::apply`]. Generated
+    /// dispatchers apply the accounts of an instruction once its handler
+    /// succeeded, so a failing handler never writes them.
+    dirty: bool,
 }
 
 impl<const DISCRIMINATOR_SIZE: usize, T> AccountData<DISCRIMINATOR_SIZE, T>
@@ -32,7 +45,10 @@ where
     T: AccountDataConfig<DISCRIMINATOR_SIZE>,
 {
     pub fn new(inner: T) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            dirty: false,
+        }
     }
 
     #[cfg(feature = "std")]
@@ -62,12 +78,15 @@ where
         }
         // SAFETY: the account data size is already checked
         let (discriminator, data) = unsafe { split_at_fixed_unchecked(bytes) };
+        if discriminator == &[CLOSED_DISCRIMINATOR_BYTE; DISCRIMINATOR_SIZE] {
+            return Err(ErrorCode::AccountClosed.into());
+        }
         if discriminator != &T::DISCRIMINATOR {
             return Err(ProgramError::InvalidAccountData);
         }
-        Ok(Self {
-            inner: T::try_from_slice(data).map_err(|_err| ProgramError::BorshIoError)?,
-        })
+        Ok(Self::new(
+            T::try_from_slice(data).map_err(|_err| ProgramError::BorshIoError)?,
+        ))
     }
 
     fn serialize<'info, M, S>(
@@ -78,24 +97,32 @@ where
         T: BorshSerialize,
         M: AccountWrite,
     {
-        let account_data = &mut account_info.data_mut()[..];
-        if account_data.len() < T::DATA_SIZE + DISCRIMINATOR_SIZE {
-            return Err(ProgramError::InvalidAccountData);
-        }
-        // SAFETY: the account data size is already checked
-        let mut data = unsafe {
-            let (discriminator, data) = account_data.split_at_mut_unchecked(DISCRIMINATOR_SIZE);
-            ptr::copy_nonoverlapping(
-                T::DISCRIMINATOR.as_ptr(),
-                discriminator.as_mut_ptr(),
-                DISCRIMINATOR_SIZE,
-            );
-            data
-        };
-        BorshSerialize::serialize(&self.inner, &mut data)
-            .map_err(|_err| ProgramError::BorshIoError)?;
-        Ok(())
+        write_data::<DISCRIMINATOR_SIZE, T>(&self.inner, account_info.data_mut())
+    }
+}
+
+/// Writes the discriminator and `inner` to `account_data`.
+fn write_data<const DISCRIMINATOR_SIZE: usize, T>(
+    inner: &T,
+    account_data: &mut [u8],
+) -> Result<(), ProgramError>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE> + BorshSerialize,
+{
+    if account_data.len() < T::DATA_SIZE + DISCRIMINATOR_SIZE {
+        return Err(ProgramError::InvalidAccountData);
     }
+    // SAFETY: the account data size is already checked
+    let mut data = unsafe {
+        let (discriminator, data) = account_data.split_at_mut_unchecked(DISCRIMINATOR_SIZE);
+        ptr::copy_nonoverlapping(
+            T::DISCRIMINATOR.as_ptr(),
+            discriminator.as_mut_ptr(),
+            DISCRIMINATOR_SIZE,
+        );
+        data
+    };
+    BorshSerialize::serialize(inner, &mut data).map_err(|_err| ProgramError::BorshIoError)
 }
 
 impl<'info, const DISCRIMINATOR_SIZE: usize, T, P, S>
@@ -122,145 +149,303 @@ where
         &self.inner.inner
     }
 
+    /// Borrows the data mutably, marking the account as changed.
+    ///
+    /// The change is only written to the account by [`Account::apply`],
+    /// which the dispatcher calls for the accounts of an instruction once its
+    /// handler succeeded. A CPI made before that sees the data as it was, so
+    /// apply the account first when the called program reads it.
     pub fn as_ref_mut(&mut self) -> &mut T
     where
+        T: BorshSerialize,
         P: AccountWrite,
     {
+        self.inner.dirty = true;
         &mut self.inner.inner
     }
 
-    pub fn apply(
-        mut self,
-    ) -> Result<Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Immutable, S>, ProgramError>
+    /// Writes the data to the account if it was changed since it was loaded
+    /// or last applied.
+    pub fn apply(&mut self) -> Result<(), ProgramError>
     where
         T: BorshSerialize,
         P: AccountWrite,
     {
-        AccountData::serialize(&self.inner, &mut self.account_info)?;
-        Ok(Account {
-            inner: self.inner,
-            account_info: self.account_info.to_read(),
-        })
+        if self.inner.dirty {
+            AccountData::serialize(&self.inner, &mut self.account_info)?;
+            self.inner.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Whether the data was changed since it was loaded or last applied.
+    ///
+    /// Generated dispatchers check that the handler applied its `manual_apply`
+    /// accounts once it succeeded, in debug builds.
+    pub fn is_dirty(&self) -> bool {
+        self.inner.dirty
     }
 
+    /// Drops the changes made to the data since it was last applied, so they
+    /// are never written.
+    pub fn discard(&mut self) {
+        self.inner.dirty = false;
+    }
+
+    /// Closes the account, moving its lamports to `signer` and overwriting
+    /// its data with the closed discriminator.
     pub fn close<D>(
-        self,
+        mut self,
         signer: &mut Account<'info, D, Mutable, Signed>,
     ) -> Result<(), ProgramError>
     where
         P: AccountWrite + AccountRead,
     {
-        let lamports = self.account_info.lamports();
-        signer.account_info.add_lamports(lamports)?;
-        self.account_info.close();
-        Ok(())
+        // the closed discriminator must not be overwritten by the data
+        self.inner.dirty = false;
+        close_data::<DISCRIMINATOR_SIZE, _, _, _>(&mut self.account_info, &mut signer.account_info)
     }
 }
 
-impl<'info, const DISCRIMINATOR_SIZE: usize, T>
-    Account<'info, PhantomData<AccountData<DISCRIMINATOR_SIZE, T>>, Init, Unsigned>
+impl<'info, const DISCRIMINATOR_SIZE: usize, T, S>
+    Account<'info, PhantomData<AccountData<DISCRIMINATOR_SIZE, T>>, Init, S>
 where
     T: AccountDataConfig<DISCRIMINATOR_SIZE>,
 {
-    pub fn new_init(account_info: AccountInfo<'info, Init, Unsigned>) -> Self {
+    pub fn new_init(account_info: AccountInfo<'info, Init, S>) -> Self {
         Self {
             inner: PhantomData,
             account_info,
         }
     }
 
+    /// Creates the account at the program derived address of `seeds` and
+    /// `bump`, funded by `payer`, and writes `account` to it.
+    ///
+    /// The created account holds the lock of this one. The dispatcher never
+    /// applies it, so later changes to it are only written by
+    /// [`Account::apply`].
     pub fn init<P>(
-        mut self,
+        &mut self,
         account: T,
+        seeds: &[&[u8]],
         bump: u8,
         payer: &mut Account<'info, P, Mutable, Signed>,
         owner: &Pubkey,
-    ) -> Result<Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Immutable, Unsigned>, ProgramError>
+    ) -> Result<Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, S>, ProgramError>
     where
         T: BorshSerialize,
     {
-        self.account_info.while_released(|account_info| {
-            if !account_info.data_is_empty() {
-                return Err(ProgramError::AccountAlreadyInitialized);
-            }
-
-            // TODO: set seed
-            let seed = b"todo";
-            let seeds = [seed.as_slice(), &[bump]];
-            let pda = pubkey::create_program_address(&seeds, owner)?;
+        self.create(account, seeds, bump, payer, owner)
+    }
 
-            if *account_info.key() != pda {
-                return Err(ProgramError::IllegalOwner);
-            }
+    /// Creates the account like [`Account::init`].
+    pub(super) fn create<P>(
+        &mut self,
+        account: T,
+        seeds: &[&[u8]],
+        bump: u8,
+        payer: &mut Account<'info, P, Mutable, Signed>,
+        owner: &Pubkey,
+    ) -> Result<Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, S>, ProgramError>
+    where
+        T: BorshSerialize,
+    {
+        self.account_info.while_released(|account_info| {
+            check_uninitialized(account_info)?;
 
-            let rent = Rent::get()?;
-            let required_lamports = rent.minimum_balance(T::DATA_SIZE + DISCRIMINATOR_SIZE);
+            check_address(account_info.key(), seeds, bump, owner)?;
+            let bump = [bump];
 
             payer.account_info.while_released(|payer| {
-                CreateAccount {
-                    from: payer,
-                    to: account_info,
-                    lamports: required_lamports,
-                    space: (T::DATA_SIZE + DISCRIMINATOR_SIZE) as u64,
-                    owner,
-                }
-                .invoke_signed(&[Signer::from(&[Seed::from(seed), Seed::from(&[bump])])])?;
-                Ok(())
+                cpi::with_signer(seeds, &bump, |signers| {
+                    create_account::<DISCRIMINATOR_SIZE, T>(payer, account_info, owner, signers)
+                })?
             })
         })?;
+        self.write_init(account)
+    }
 
-        let account = AccountData { inner: account };
-        AccountData::serialize(&account, &mut self.account_info)?;
+    /// Writes the data of the account once it was created, taking over its
+    /// lock.
+    fn write_init(
+        &mut self,
+        account: T,
+    ) -> Result<Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, S>, ProgramError>
+    where
+        T: BorshSerialize,
+    {
+        let account = AccountData::new(account);
+        let mut account_info = self.account_info.take_created();
+        AccountData::serialize(&account, &mut account_info)?;
 
         Ok(Account {
             inner: account,
-            account_info: self.account_info.to_read(),
+            account_info,
         })
     }
+}
 
-    pub fn init2<P>(
-        mut self,
+impl<'info, const DISCRIMINATOR_SIZE: usize, T>
+    Account<'info, PhantomData<AccountData<DISCRIMINATOR_SIZE, T>>, Init, Signed>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE>,
+{
+    /// Creates the account at the key of the keypair that signed the
+    /// transaction, funded by `payer`, and writes `account` to it.
+    ///
+    /// Like [`Account::init`], later changes to the created account are left
+    /// to [`Account::apply`].
+    pub fn init_keypair<P>(
+        &mut self,
         account: T,
-        bump: u8,
         payer: &mut Account<'info, P, Mutable, Signed>,
         owner: &Pubkey,
-    ) -> Result<(), ProgramError>
+    ) -> Result<Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, Signed>, ProgramError>
     where
         T: BorshSerialize,
     {
         self.account_info.while_released(|account_info| {
-            if !account_info.data_is_empty() {
-                return Err(ProgramError::AccountAlreadyInitialized);
-            }
+            check_uninitialized(account_info)?;
+            payer.account_info.while_released(|payer| {
+                create_account::<DISCRIMINATOR_SIZE, T>(payer, account_info, owner, &[])
+            })
+        })?;
+        self.write_init(account)
+    }
+}
+
+/// Checks that `account_info` can still be created.
+fn check_uninitialized(
+    account_info: &pinocchio::account_info::AccountInfo,
+) -> Result<(), ProgramError> {
+    // an account holding data, or owned by a program, was already initialized
+    // and must not be created again
+    if !account_info.data_is_empty() || !account_info.is_owned_by(&pinocchio_system::ID) {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    Ok(())
+}
+
+/// Creates `to` as a rent exempt account of `T`, funded by `from`.
+///
+/// Anyone can send lamports to the address of an account before it is
+/// created, which would fail `CreateAccount`. An account already holding
+/// lamports is instead topped up to the rent exempt minimum, then allocated
+/// and assigned to `owner`.
+fn create_account<const DISCRIMINATOR_SIZE: usize, T>(
+    from: &pinocchio::account_info::AccountInfo,
+    to: &pinocchio::account_info::AccountInfo,
+    owner: &Pubkey,
+    signers: &[Signer],
+) -> Result<(), ProgramError>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE>,
+{
+    let space = T::DATA_SIZE + DISCRIMINATOR_SIZE;
+    let required_lamports = Rent::get()?.minimum_balance(space);
 
-            // TODO: set seed
-            let seed = b"todo";
-            let seeds = [seed.as_slice(), &[bump]];
-            let pda = pubkey::create_program_address(&seeds, owner)?;
+    let lamports = to.lamports();
+    if lamports == 0 {
+        return CreateAccount {
+            from,
+            to,
+            lamports: required_lamports,
+            space: space as u64,
+            owner,
+        }
+        .invoke_signed(signers);
+    }
 
-            if *account_info.key() != pda {
-                return Err(ProgramError::IllegalOwner);
-            }
+    if lamports < required_lamports {
+        Transfer {
+            from,
+            to,
+            lamports: required_lamports - lamports,
+        }
+        .invoke_signed(signers)?;
+    }
+    Allocate {
+        account: to,
+        space: space as u64,
+    }
+    .invoke_signed(signers)?;
+    Assign { account: to, owner }.invoke_signed(signers)
+}
 
-            let rent = Rent::get()?;
-            let required_lamports = rent.minimum_balance(T::DATA_SIZE + DISCRIMINATOR_SIZE);
+/// Closes an account that is no longer loaded, moving its lamports to
+/// `destination` and overwriting its data with the closed discriminator.
+///
+/// Generated dispatchers call this for the `close` accounts of an instruction
+/// once its handler succeeded.
+pub fn close_account<'info, const DISCRIMINATOR_SIZE: usize>(
+    account: &'info pinocchio::account_info::AccountInfo,
+    destination: &'info pinocchio::account_info::AccountInfo,
+) -> Result<(), ProgramError> {
+    let mut account = AccountInfo::<Mutable, Unsigned>::new(account)?;
+    let mut destination = AccountInfo::<Mutable, Unsigned>::new(destination)?;
+    close_data::<DISCRIMINATOR_SIZE, _, _, _>(&mut account, &mut destination)
+}
 
-            payer.account_info.while_released(|payer| {
-                CreateAccount {
-                    from: payer,
-                    to: account_info,
-                    lamports: required_lamports,
-                    space: (T::DATA_SIZE + DISCRIMINATOR_SIZE) as u64,
-                    owner,
-                }
-                .invoke_signed(&[Signer::from(&[Seed::from(seed), Seed::from(&[bump])])])?;
-                Ok(())
-            })
-        })?;
+/// Checks that an account that is no longer loaded is rent exempt, or was
+/// emptied of all its lamports.
+///
+/// Generated dispatchers call this for the program accounts an instruction
+/// writes, including its mutable remaining accounts, once its handler
+/// succeeded.
+pub fn check_rent_exempt(
+    account: &pinocchio::account_info::AccountInfo,
+) -> Result<(), ProgramError> {
+    let lamports = account.lamports();
+    if lamports != 0 && lamports < Rent::get()?.minimum_balance(account.data_len()) {
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    Ok(())
+}
 
-        let account = AccountData { inner: account };
-        AccountData::serialize(&account, &mut self.account_info)?;
+/// Zeroes the data of `account` and writes the closed discriminator, so the
+/// account can't be revived later in the transaction, then moves its lamports
+/// to `destination`.
+fn close_data<const DISCRIMINATOR_SIZE: usize, M, S, D>(
+    account: &mut AccountInfo<'_, M, S>,
+    destination: &mut AccountInfo<'_, Mutable, D>,
+) -> Result<(), ProgramError>
+where
+    M: AccountWrite,
+{
+    let data = account.data_mut();
+    data.fill(0);
+    let len = data.len().min(DISCRIMINATOR_SIZE);
+    data[..len].fill(CLOSED_DISCRIMINATOR_BYTE);
 
-        Ok(())
+    let lamports = account.zero_out_lamports()?;
+    destination.add_lamports(lamports)
+}
+
+/// Checks that `key` is the program derived address of `seeds` and `bump`,
+/// failing with [`ProgramError::InvalidSeeds`] otherwise.
+///
+/// Generated dispatchers call this for the loaded accounts an instruction
+/// declares a `seed` for, with the bump stored in the account.
+pub fn check_address(
+    key: &Pubkey,
+    seeds: &[&[u8]],
+    bump: u8,
+    owner: &Pubkey,
+) -> Result<(), ProgramError> {
+    if derive_address(seeds, &[bump], owner)? != *key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
+
+/// Returns the program derived address of `seeds` and `bump`.
+fn derive_address(seeds: &[&[u8]], bump: &[u8; 1], owner: &Pubkey) -> Result<Pubkey, ProgramError> {
+    if seeds.len() >= MAX_SEEDS {
+        return Err(ProgramError::MaxSeedLengthExceeded);
     }
+    let mut all_seeds: [&[u8]; MAX_SEEDS] = [&[]; MAX_SEEDS];
+    all_seeds[..seeds.len()].copy_from_slice(seeds);
+    all_seeds[seeds.len()] = bump;
+    pubkey::create_program_address(&all_seeds[..=seeds.len()], owner)
 }
@@ -0,0 +1,89 @@
+use core::marker::PhantomData;
+
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::error::ErrorCode;
+
+/// A program with a well known address.
+pub trait ProgramId {
+    const ID: Pubkey;
+}
+
+/// The system program.
+pub struct System;
+
+/// The SPL token program.
+pub struct Token;
+
+/// The SPL Token-2022 program.
+pub struct Token2022;
+
+/// The SPL associated token account program.
+pub struct AssociatedToken;
+
+/// A program owning token accounts and mints, either [`Token`] or [`Token2022`].
+pub trait TokenProgram: ProgramId {}
+
+impl TokenProgram for Token {}
+
+impl TokenProgram for Token2022 {}
+
+impl ProgramId for System {
+    const ID: Pubkey = pinocchio_system::ID;
+}
+
+impl ProgramId for Token {
+    const ID: Pubkey = [
+        6, 221, 246, 225, 215, 101, 161, 147, 217, 203, 225, 70, 206, 235, 121, 172, 28, 180, 133,
+        237, 95, 91, 55, 145, 58, 140, 245, 133, 126, 255, 0, 169,
+    ];
+}
+
+impl ProgramId for Token2022 {
+    const ID: Pubkey = [
+        6, 221, 246, 225, 238, 117, 143, 222, 24, 66, 93, 188, 228, 108, 205, 218, 182, 26, 252,
+        77, 131, 185, 13, 39, 254, 189, 249, 40, 216, 161, 139, 252,
+    ];
+}
+
+impl ProgramId for AssociatedToken {
+    const ID: Pubkey = [
+        140, 151, 37, 143, 78, 36, 137, 241, 187, 61, 16, 41, 20, 142, 13, 131, 11, 90, 19, 153,
+        218, 255, 16, 132, 4, 142, 123, 216, 219, 233, 248, 89,
+    ];
+}
+
+/// A program account passed to an instruction, typically so it can be invoked.
+///
+/// The account key is checked against `P::ID` when the account is loaded,
+/// failing with [`ErrorCode::ConstraintAddress`] like an account at the wrong
+/// `address`.
+pub struct Program<'info, P> {
+    account_info: &'info AccountInfo,
+    program: PhantomData<P>,
+}
+
+impl<'info, P> Program<'info, P>
+where
+    P: ProgramId,
+{
+    pub fn new(account_info: &'info AccountInfo) -> Result<Self, ProgramError> {
+        if account_info.key() != &P::ID {
+            return Err(ErrorCode::ConstraintAddress.into());
+        }
+        Ok(Program {
+            account_info,
+            program: PhantomData,
+        })
+    }
+}
+
+impl<'info, P> Program<'info, P> {
+    pub fn key(&self) -> &Pubkey {
+        self.account_info.key()
+    }
+
+    pub fn account_info(&self) -> &'info AccountInfo {
+        self.account_info
+    }
+}
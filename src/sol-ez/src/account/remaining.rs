@@ -0,0 +1,296 @@
+use core::{marker::PhantomData, mem::MaybeUninit, ptr::NonNull, slice};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pinocchio::{
+    account_info::AccountInfo,
+    entrypoint::{InstructionContext, MaybeAccount},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    account_info::{Immutable, Mutable, Unsigned},
+    error::ErrorCode,
+};
+
+use super::{Account, AccountBuilder, AccountData, AccountDataConfig};
+
+/// The accounts passed after the fixed accounts of an instruction.
+///
+/// Accounts are only loaded as they are iterated, checking their owner,
+/// discriminator and, for `Mutable` accounts, that they are writable. An
+/// account that fails to load is yielded as an error, so an instruction can
+/// stop at the first bad account with `?`. A `Mutable` account already passed
+/// as a fixed account or yielded before fails with
+/// [`ErrorCode::DuplicateAccount`].
+///
+/// The dispatcher never sees the yielded accounts, so changes to `Mutable`
+/// accounts are left to [`Account::apply`], see
+/// [`RemainingAccounts::update_each`].
+pub struct RemainingAccounts<'info, const DISCRIMINATOR_SIZE: usize, T, P> {
+    source: Source<'info>,
+    owner: Pubkey,
+    payload: PhantomData<T>,
+    mutable: PhantomData<P>,
+}
+
+/// Where the remaining accounts come from.
+enum Source<'info> {
+    /// Accounts the entrypoint already parsed.
+    Parsed {
+        accounts: &'info [AccountInfo],
+        offset: usize,
+        next: usize,
+    },
+    /// Accounts parsed from the input of the lazy entrypoint as they are
+    /// iterated.
+    Lazy(LazyAccounts<'info>),
+}
+
+/// The unparsed accounts of the lazy entrypoint, and the buffer they are
+/// parsed into.
+struct LazyAccounts<'info> {
+    context: InstructionContext,
+    fixed: &'info [AccountInfo],
+    buffer: NonNull<MaybeUninit<AccountInfo>>,
+    capacity: usize,
+    len: usize,
+    borrow: PhantomData<&'info mut [MaybeUninit<AccountInfo>]>,
+}
+
+impl<'info> LazyAccounts<'info> {
+    /// The accounts parsed so far.
+    fn parsed(&self) -> &'info [AccountInfo] {
+        // SAFETY: the first `len` accounts of the buffer are initialized, and
+        // never written again
+        unsafe { slice::from_raw_parts(self.buffer.as_ptr() as *const AccountInfo, self.len) }
+    }
+
+    fn next(&mut self, unique: bool) -> Option<Result<&'info AccountInfo, ProgramError>> {
+        if self.context.remaining() == 0 {
+            return None;
+        }
+        if self.len == self.capacity {
+            return Some(Err(ProgramError::InvalidArgument));
+        }
+        let account = match self.context.next_account() {
+            Ok(MaybeAccount::Account(account)) => account,
+            Ok(MaybeAccount::Duplicated(_)) if unique => {
+                return Some(Err(ErrorCode::DuplicateAccount.into()));
+            }
+            // duplicates always refer to an earlier account
+            Ok(MaybeAccount::Duplicated(original)) => {
+                let original = original as usize;
+                let account = match original.checked_sub(self.fixed.len()) {
+                    None => self.fixed.get(original),
+                    Some(original) => self.parsed().get(original),
+                };
+                match account {
+                    Some(account) => account.clone(),
+                    None => return Some(Err(ProgramError::InvalidArgument)),
+                }
+            }
+            Err(err) => return Some(Err(err)),
+        };
+        // SAFETY: `len` is below the capacity of the buffer, and no reference
+        // to the slot was handed out yet
+        let account = unsafe {
+            let slot = self.buffer.add(self.len).as_mut();
+            slot.write(account)
+        };
+        self.len += 1;
+        Some(Ok(account))
+    }
+}
+
+impl<'info> Source<'info> {
+    /// Returns the next account, or a [`ErrorCode::DuplicateAccount`] error if
+    /// it is `unique` but was passed before.
+    fn next(&mut self, unique: bool) -> Option<Result<&'info AccountInfo, ProgramError>> {
+        match self {
+            Source::Parsed { accounts, next, .. } => {
+                let idx = *next;
+                let account = accounts.get(idx)?;
+                *next += 1;
+                if unique
+                    && accounts[..idx]
+                        .iter()
+                        .any(|other| other.key() == account.key())
+                {
+                    return Some(Err(ErrorCode::DuplicateAccount.into()));
+                }
+                Some(Ok(account))
+            }
+            Source::Lazy(lazy) => lazy.next(unique),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Source::Parsed { accounts, next, .. } => accounts.len() - next,
+            Source::Lazy(lazy) => lazy.context.remaining() as usize,
+        }
+    }
+
+    fn iterated(&self) -> &'info [AccountInfo] {
+        match self {
+            Source::Parsed {
+                accounts,
+                offset,
+                next,
+            } => &accounts[*offset..*next],
+            Source::Lazy(lazy) => lazy.parsed(),
+        }
+    }
+}
+
+impl<'info, const DISCRIMINATOR_SIZE: usize, T, P>
+    RemainingAccounts<'info, DISCRIMINATOR_SIZE, T, P>
+{
+    /// Wraps the accounts after the first `offset` accounts, which must be
+    /// owned by `owner`.
+    pub fn new(accounts: &'info [AccountInfo], offset: usize, owner: &Pubkey) -> Self {
+        let offset = offset.min(accounts.len());
+        Self::from_source(
+            Source::Parsed {
+                accounts,
+                offset,
+                next: offset,
+            },
+            owner,
+        )
+    }
+
+    /// Wraps the accounts of the lazy entrypoint left in `context` after the
+    /// `fixed` accounts, which must be owned by `owner`.
+    ///
+    /// The accounts are parsed into `buffer` as they are iterated, so it must
+    /// have room for every account left in `context`.
+    pub fn lazy(
+        context: InstructionContext,
+        fixed: &'info [AccountInfo],
+        buffer: &'info mut [MaybeUninit<AccountInfo>],
+        owner: &Pubkey,
+    ) -> Self {
+        let capacity = buffer.len();
+        Self::from_source(
+            Source::Lazy(LazyAccounts {
+                context,
+                fixed,
+                buffer: NonNull::from(buffer).cast(),
+                capacity,
+                len: 0,
+                borrow: PhantomData,
+            }),
+            owner,
+        )
+    }
+
+    fn from_source(source: Source<'info>, owner: &Pubkey) -> Self {
+        Self {
+            source,
+            owner: *owner,
+            payload: PhantomData,
+            mutable: PhantomData,
+        }
+    }
+
+    /// The accounts iterated so far, including the ones that failed to load.
+    ///
+    /// The accounts that were not iterated can't have been changed by the
+    /// instruction.
+    pub fn iterated(&self) -> &'info [AccountInfo] {
+        self.source.iterated()
+    }
+}
+
+impl<'info, const DISCRIMINATOR_SIZE: usize, T> Iterator
+    for RemainingAccounts<'info, DISCRIMINATOR_SIZE, T, Immutable>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE> + BorshDeserialize,
+{
+    type Item = Result<
+        Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Immutable, Unsigned>,
+        ProgramError,
+    >;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let owner = &self.owner;
+        Some(self.source.next(false)?.and_then(|account_info| {
+            AccountBuilder::new(account_info)
+                .owner(owner)
+                .and_then(|account| account.set_payload().build())
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.source.len();
+        (len, Some(len))
+    }
+}
+
+impl<'info, const DISCRIMINATOR_SIZE: usize, T> Iterator
+    for RemainingAccounts<'info, DISCRIMINATOR_SIZE, T, Mutable>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE> + BorshDeserialize,
+{
+    type Item =
+        Result<Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, Unsigned>, ProgramError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let owner = &self.owner;
+        Some(self.source.next(true)?.and_then(|account_info| {
+            AccountBuilder::new(account_info)
+                .owner(owner)
+                .and_then(|account| account.set_payload().mutable())
+                .and_then(|account| account.build())
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.source.len();
+        (len, Some(len))
+    }
+}
+
+impl<'info, const DISCRIMINATOR_SIZE: usize, T>
+    RemainingAccounts<'info, DISCRIMINATOR_SIZE, T, Mutable>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE> + BorshDeserialize + BorshSerialize,
+{
+    /// Loads every remaining account in turn and calls `f` with it, applying
+    /// the changes `f` made.
+    ///
+    /// Stops at the first error, discarding the changes made to the account
+    /// that failed.
+    pub fn update_each(
+        &mut self,
+        mut f: impl FnMut(
+            &mut Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, Unsigned>,
+        ) -> Result<(), ProgramError>,
+    ) -> Result<(), ProgramError> {
+        for account in self {
+            let mut account = account?;
+            if let Err(err) = f(&mut account) {
+                account.discard();
+                return Err(err);
+            }
+            account.apply()?;
+        }
+        Ok(())
+    }
+}
+
+impl<const DISCRIMINATOR_SIZE: usize, T> ExactSizeIterator
+    for RemainingAccounts<'_, DISCRIMINATOR_SIZE, T, Immutable>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE> + BorshDeserialize,
+{
+}
+
+impl<const DISCRIMINATOR_SIZE: usize, T> ExactSizeIterator
+    for RemainingAccounts<'_, DISCRIMINATOR_SIZE, T, Mutable>
+where
+    T: AccountDataConfig<DISCRIMINATOR_SIZE> + BorshDeserialize,
+{
+}
@@ -0,0 +1,177 @@
+use core::marker::PhantomData;
+
+use pinocchio::{
+    account_info::{AccountInfo, Ref},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::error::ErrorCode;
+
+pub use pinocchio::sysvars::{
+    clock::Clock,
+    instructions::{IntrospectedAccountMeta, IntrospectedInstruction},
+    rent::Rent,
+};
+
+/// The instructions of the executing transaction, read from the instructions
+/// sysvar.
+pub type IntrospectedInstructions<'info> =
+    pinocchio::sysvars::instructions::Instructions<Ref<'info, [u8]>>;
+
+/// The epoch schedule sysvar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EpochSchedule {
+    /// The number of slots in each epoch after the warmup.
+    pub slots_per_epoch: u64,
+    /// The number of slots before an epoch its leader schedule is computed.
+    pub leader_schedule_slot_offset: u64,
+    /// Whether epochs start short and grow during the warmup.
+    pub warmup: bool,
+    /// The first epoch of `slots_per_epoch` slots.
+    pub first_normal_epoch: u64,
+    /// The first slot of `first_normal_epoch`.
+    pub first_normal_slot: u64,
+}
+
+impl EpochSchedule {
+    pub const LEN: usize = 33;
+
+    fn from_bytes(data: &[u8]) -> Result<Self, ProgramError> {
+        let data: &[u8; Self::LEN] = data
+            .get(..Self::LEN)
+            .and_then(|data| data.try_into().ok())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let u64_at = |offset: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&data[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        Ok(EpochSchedule {
+            slots_per_epoch: u64_at(0),
+            leader_schedule_slot_offset: u64_at(8),
+            warmup: data[16] != 0,
+            first_normal_epoch: u64_at(17),
+            first_normal_slot: u64_at(25),
+        })
+    }
+}
+
+/// The instructions sysvar, listing the instructions of the executing
+/// transaction.
+pub struct Instructions;
+
+/// A sysvar with a well known address.
+pub trait SysvarId {
+    const ID: Pubkey;
+}
+
+impl SysvarId for Clock {
+    const ID: Pubkey = pinocchio::sysvars::clock::CLOCK_ID;
+}
+
+impl SysvarId for Rent {
+    const ID: Pubkey = pinocchio::sysvars::rent::RENT_ID;
+}
+
+impl SysvarId for EpochSchedule {
+    const ID: Pubkey = [
+        6, 167, 213, 23, 24, 220, 63, 238, 2, 211, 228, 127, 1, 0, 248, 176, 84, 247, 148, 46, 96,
+        89, 30, 63, 80, 135, 25, 168, 5, 0, 0, 0,
+    ];
+}
+
+impl SysvarId for Instructions {
+    const ID: Pubkey = pinocchio::sysvars::instructions::INSTRUCTIONS_ID;
+}
+
+/// A sysvar account passed to an instruction.
+///
+/// The account key is checked against `T::ID` when the account is loaded,
+/// failing with [`ErrorCode::ConstraintAddress`] like an account at the wrong
+/// `address`.
+pub struct Sysvar<'info, T> {
+    account_info: &'info AccountInfo,
+    sysvar: PhantomData<T>,
+}
+
+impl<'info, T> Sysvar<'info, T>
+where
+    T: SysvarId,
+{
+    pub fn new(account_info: &'info AccountInfo) -> Result<Self, ProgramError> {
+        if account_info.key() != &T::ID {
+            return Err(ErrorCode::ConstraintAddress.into());
+        }
+        Ok(Sysvar {
+            account_info,
+            sysvar: PhantomData,
+        })
+    }
+}
+
+impl<'info, T> Sysvar<'info, T> {
+    pub fn key(&self) -> &Pubkey {
+        self.account_info.key()
+    }
+
+    pub fn account_info(&self) -> &'info AccountInfo {
+        self.account_info
+    }
+}
+
+impl Sysvar<'_, Clock> {
+    pub fn get(&self) -> Result<Clock, ProgramError> {
+        Clock::from_account_info(self.account_info).map(|clock| *clock)
+    }
+}
+
+impl Sysvar<'_, Rent> {
+    pub fn get(&self) -> Result<Rent, ProgramError> {
+        Rent::from_account_info(self.account_info).map(|rent| *rent)
+    }
+}
+
+impl Sysvar<'_, EpochSchedule> {
+    pub fn get(&self) -> Result<EpochSchedule, ProgramError> {
+        EpochSchedule::from_bytes(&self.account_info.try_borrow_data()?)
+    }
+}
+
+impl<'info> Sysvar<'info, Instructions> {
+    /// Borrows the instructions of the transaction, to look up the
+    /// instructions around the executing one.
+    pub fn instructions(&self) -> Result<IntrospectedInstructions<'info>, ProgramError> {
+        IntrospectedInstructions::try_from(self.account_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_schedule_from_bytes() {
+        let mut data = [0; EpochSchedule::LEN];
+        data[..8].copy_from_slice(&432_000u64.to_le_bytes());
+        data[8..16].copy_from_slice(&432_000u64.to_le_bytes());
+        data[16] = 1;
+        data[17..25].copy_from_slice(&14u64.to_le_bytes());
+        data[25..].copy_from_slice(&524_256u64.to_le_bytes());
+
+        assert_eq!(
+            EpochSchedule::from_bytes(&data),
+            Ok(EpochSchedule {
+                slots_per_epoch: 432_000,
+                leader_schedule_slot_offset: 432_000,
+                warmup: true,
+                first_normal_epoch: 14,
+                first_normal_slot: 524_256,
+            })
+        );
+        assert_eq!(
+            EpochSchedule::from_bytes(&data[..32]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
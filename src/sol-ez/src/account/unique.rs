@@ -0,0 +1,38 @@
+use pinocchio::{
+    account_info::AccountInfo, log::sol_log, program_error::ProgramError, pubkey::Pubkey,
+};
+
+use crate::error::ErrorCode;
+
+/// Checks that no `mutable` account of an instruction is passed as any other
+/// of its `mutable` or `others` accounts, given by index and field name.
+///
+/// The names of colliding fields are logged. Accounts passed as the program
+/// id are skipped, as that is the placeholder of an absent optional account.
+pub fn check_unique(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mutable: &[(usize, &str)],
+    others: &[(usize, &str)],
+) -> Result<(), ProgramError> {
+    let key = |idx: usize| {
+        accounts
+            .get(idx)
+            .map(AccountInfo::key)
+            .filter(|key| *key != program_id)
+    };
+    for (pos, (idx, name)) in mutable.iter().enumerate() {
+        let Some(account) = key(*idx) else {
+            continue;
+        };
+        for (other_idx, other_name) in mutable[pos + 1..].iter().chain(others) {
+            if key(*other_idx) == Some(account) {
+                sol_log("duplicate accounts:");
+                sol_log(name);
+                sol_log(other_name);
+                return Err(ErrorCode::DuplicateAccount.into());
+            }
+        }
+    }
+    Ok(())
+}
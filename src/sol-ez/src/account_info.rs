@@ -137,6 +137,8 @@ pub mod markers {
 struct AccountGuard<'info> {
     data: Option<pinocchio::RefMut<'info, [u8]>>,
     lamports: Option<pinocchio::RefMut<'info, u64>>,
+    /// Whether the account is locked at all, see [`AccountGuard::unlocked`].
+    locked: bool,
 }
 
 impl<'info> AccountGuard<'info> {
@@ -153,9 +155,20 @@ impl<'info> AccountGuard<'info> {
         Ok(Self {
             data: Some(account_info.try_borrow_mut_data()?),
             lamports: Some(account_info.try_borrow_mut_lamports()?),
+            locked: true,
         })
     }
 
+    /// A guard that never locks the account, for a read only account that is
+    /// allowed to alias another account of the instruction.
+    pub fn unlocked() -> Self {
+        Self {
+            data: None,
+            lamports: None,
+            locked: false,
+        }
+    }
+
     /// Releases the lock on the account's data and lamports, allowing other parts
     /// of the program to borrow the account again.
     pub fn release(&mut self) {
@@ -216,6 +229,21 @@ impl<'info, M, S> AccountInfo<'info, M, S> {
         })
     }
 
+    /// Wraps an account without locking it, so it may also be passed as
+    /// another account of the instruction.
+    ///
+    /// Only read only accounts may be unlocked. Their reads see the writes
+    /// made through the account they alias.
+    #[inline(always)]
+    pub(crate) fn new_unlocked(account_info: &'info pinocchio::AccountInfo) -> Self {
+        AccountInfo {
+            inner: account_info,
+            guard: AccountGuard::unlocked(),
+            _mutable_marker: PhantomData,
+            _signed_markser: PhantomData,
+        }
+    }
+
     #[inline(always)]
     pub fn to_raw_account_info(self) -> &'info pinocchio::AccountInfo {
         self.inner
@@ -225,14 +253,34 @@ impl<'info, M, S> AccountInfo<'info, M, S> {
         &mut self,
         f: impl FnOnce(&pinocchio::AccountInfo) -> Result<(), pinocchio::ProgramError>,
     ) -> Result<(), pinocchio::ProgramError> {
-        self.guard.release();
+        self.release();
         f(self.inner)?;
-        self.guard = AccountGuard::lock(self.inner)?;
+        self.relock()
+    }
+
+    /// Returns the raw account without consuming the wrapper.
+    #[inline(always)]
+    pub(crate) fn raw(&self) -> &'info pinocchio::AccountInfo {
+        self.inner
+    }
+
+    /// Releases the guard so the account can be borrowed by a CPI.
+    #[inline(always)]
+    pub(crate) fn release(&mut self) {
+        self.guard.release();
+    }
+
+    /// Re-acquires the guard after [`AccountInfo::release`].
+    #[inline(always)]
+    pub(crate) fn relock(&mut self) -> Result<(), pinocchio::ProgramError> {
+        if self.guard.locked {
+            self.guard = AccountGuard::lock(self.inner)?;
+        }
         Ok(())
     }
 
     #[inline(always)]
-    pub fn key(&self) -> &pinocchio::Pubkey {
+    pub fn key(&self) -> &'info pinocchio::Pubkey {
         self.inner.key()
     }
 
@@ -360,7 +408,7 @@ impl<'info, M, S> AccountInfo<'info, M, S> {
     }
 }
 
-impl<'info, S> AccountInfo<'info, Init, S> {
+impl<'info> AccountInfo<'info, Init, Unsigned> {
     #[inline(always)]
     pub fn new_init(
         account_info: &'info pinocchio::AccountInfo,
@@ -369,6 +417,34 @@ impl<'info, S> AccountInfo<'info, Init, S> {
     }
 }
 
+impl<'info> AccountInfo<'info, Init, Signed> {
+    /// Wraps an account to be created that signed the transaction, such as
+    /// the account of a new keypair.
+    #[inline(always)]
+    pub fn new_init_signed(
+        account_info: &'info pinocchio::AccountInfo,
+    ) -> Result<Self, pinocchio::ProgramError> {
+        if !account_info.is_signer() {
+            return Err(pinocchio::ProgramError::MissingRequiredSignature);
+        }
+        Self::new(account_info)
+    }
+}
+
+impl<'info, S> AccountInfo<'info, Init, S> {
+    /// Converts an account that has been created into a writable one, which
+    /// takes over the lock and leaves this one unlocked.
+    #[inline(always)]
+    pub(crate) fn take_created(&mut self) -> AccountInfo<'info, Mutable, S> {
+        AccountInfo {
+            inner: self.inner,
+            guard: core::mem::replace(&mut self.guard, AccountGuard::unlocked()),
+            _mutable_marker: PhantomData,
+            _signed_markser: PhantomData,
+        }
+    }
+}
+
 impl<'info, M, S> AccountInfo<'info, M, S>
 where
     M: AccountWrite,
@@ -0,0 +1,474 @@
+//! Typed cross-program invocation.
+//!
+//! Generated `cpi` modules take accounts as `impl CpiAccount`, `impl CpiWritable`
+//! and `impl CpiSigner`, so the writable and signer requirements of the called
+//! instruction are checked at compile time. Program derived accounts of the
+//! caller sign through a [`PdaSigner`].
+
+use core::{mem::MaybeUninit, slice};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::{self, invoke_signed, slice_invoke_signed, MAX_CPI_ACCOUNTS, MAX_RETURN_DATA},
+    instruction::{AccountMeta, Instruction, Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{Pubkey, MAX_SEEDS},
+    ProgramResult,
+};
+
+use crate::{
+    account::{Account, Program, Sysvar},
+    account_info::{AccountWrite, Signed, Unsigned},
+    error::ErrorCode,
+};
+
+/// An account that can be passed to a CPI.
+pub trait CpiAccount<'info> {
+    fn account_info(&self) -> &'info AccountInfo;
+
+    /// Releases any borrow held on the account for the duration of the CPI.
+    fn release(&mut self);
+
+    /// Re-acquires the borrow released by [`CpiAccount::release`].
+    fn relock(&mut self) -> Result<(), ProgramError>;
+
+    /// The seeds the calling program signs with, `None` for accounts that
+    /// signed the transaction.
+    fn signer_seeds(&self) -> Option<SignerSeeds<'_>> {
+        None
+    }
+}
+
+/// An account that can be passed to a CPI as writable.
+pub trait CpiWritable<'info>: CpiAccount<'info> {}
+
+/// An account that can be passed to a CPI as a signer.
+pub trait CpiSigner<'info>: CpiAccount<'info> {}
+
+/// The seeds of a program derived address, without the bump.
+pub struct SignerSeeds<'a> {
+    pub seeds: &'a [&'a [u8]],
+    pub bump: &'a [u8; 1],
+}
+
+/// A program derived account of the calling program, signing a CPI with its
+/// seeds.
+pub struct PdaSigner<'a, 'info, T, P, const N: usize> {
+    account: &'a mut Account<'info, T, P, Unsigned>,
+    seeds: [&'a [u8]; N],
+    bump: [u8; 1],
+}
+
+impl<'a, 'info, T, P, const N: usize> PdaSigner<'a, 'info, T, P, N> {
+    pub fn new(
+        account: &'a mut Account<'info, T, P, Unsigned>,
+        seeds: [&'a [u8]; N],
+        bump: u8,
+    ) -> Self {
+        PdaSigner {
+            account,
+            seeds,
+            bump: [bump],
+        }
+    }
+}
+
+/// An account passed to [`invoke`] along with how the called instruction uses it.
+pub struct CpiMeta<'a, 'info> {
+    account: Option<&'a mut dyn CpiAccount<'info>>,
+    account_info: &'info AccountInfo,
+    writable: bool,
+    signer: bool,
+}
+
+impl<'a, 'info> CpiMeta<'a, 'info> {
+    fn new(account: &'a mut dyn CpiAccount<'info>, writable: bool, signer: bool) -> Self {
+        CpiMeta {
+            account_info: account.account_info(),
+            account: Some(account),
+            writable,
+            signer,
+        }
+    }
+
+    pub fn readonly(account: &'a mut impl CpiAccount<'info>) -> Self {
+        CpiMeta::new(account, false, false)
+    }
+
+    pub fn writable(account: &'a mut impl CpiWritable<'info>) -> Self {
+        CpiMeta::new(account, true, false)
+    }
+
+    pub fn readonly_signer(account: &'a mut impl CpiSigner<'info>) -> Self {
+        CpiMeta::new(account, false, true)
+    }
+
+    pub fn writable_signer(account: &'a mut (impl CpiWritable<'info> + CpiSigner<'info>)) -> Self {
+        CpiMeta::new(account, true, true)
+    }
+
+    /// The account of the called program, in place of an optional account
+    /// that is not passed.
+    pub fn placeholder(
+        program_id: &Pubkey,
+        program: &impl CpiAccount<'info>,
+    ) -> Result<Self, ProgramError> {
+        let account_info = program.account_info();
+        if account_info.key() != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(CpiMeta {
+            account: None,
+            account_info,
+            writable: false,
+            signer: false,
+        })
+    }
+
+    fn release(&mut self) {
+        if let Some(account) = &mut self.account {
+            account.release();
+        }
+    }
+
+    fn relock(&mut self) -> Result<(), ProgramError> {
+        match &mut self.account {
+            Some(account) => account.relock(),
+            None => Ok(()),
+        }
+    }
+
+    fn signer_seeds(&self) -> Option<SignerSeeds<'_>> {
+        self.account.as_ref()?.signer_seeds()
+    }
+}
+
+/// Serializes instruction args into `data`, which must be exactly their size.
+pub fn serialize_args<T>(mut data: &mut [u8], args: &T) -> Result<(), ProgramError>
+where
+    T: BorshSerialize,
+{
+    args.serialize(&mut data)
+        .map_err(|_err| ProgramError::BorshIoError)?;
+    if !data.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(())
+}
+
+/// The size of the buffer instruction args of variable size are serialized
+/// into, the size of a transaction packet.
+pub const MAX_VARIABLE_ARGS_LEN: usize = 1232;
+
+/// Serializes instruction args of variable size into `data`, returning the
+/// number of bytes written.
+pub fn serialize_variable_args<T>(data: &mut [u8], args: &T) -> Result<usize, ProgramError>
+where
+    T: BorshSerialize,
+{
+    let mut buf = &mut data[..];
+    args.serialize(&mut buf)
+        .map_err(|_err| ProgramError::BorshIoError)?;
+    let len = buf.len();
+    Ok(data.len() - len)
+}
+
+/// Borsh serializes `value` as the return data of the instruction.
+pub fn set_return_data<T>(value: &T) -> ProgramResult
+where
+    T: BorshSerialize,
+{
+    let mut data = [0; MAX_RETURN_DATA];
+    let mut buf = &mut data[..];
+    value
+        .serialize(&mut buf)
+        .map_err(|_err| ProgramError::BorshIoError)?;
+    let len = MAX_RETURN_DATA - buf.len();
+    cpi::set_return_data(&data[..len]);
+    Ok(())
+}
+
+/// Deserializes the return data set by `program_id`, the program of the last
+/// CPI.
+///
+/// Fails with [`ErrorCode::MissingReturnData`] when no return data was set.
+pub fn get_return_data<T>(program_id: &Pubkey) -> Result<T, ProgramError>
+where
+    T: BorshDeserialize,
+{
+    let return_data = cpi::get_return_data().ok_or(ErrorCode::MissingReturnData)?;
+    if return_data.program_id() != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    T::try_from_slice(return_data.as_slice()).map_err(|_err| ProgramError::BorshIoError)
+}
+
+/// Invokes `program_id` with `data`, releasing the accounts for the duration
+/// of the call and signing for every [`PdaSigner`].
+pub fn invoke<'info, const N: usize>(
+    program_id: &Pubkey,
+    mut accounts: [CpiMeta<'_, 'info>; N],
+    data: &[u8],
+) -> ProgramResult {
+    let metas: [AccountMeta; N] = core::array::from_fn(|idx| {
+        let meta = &accounts[idx];
+        AccountMeta::new(meta.account_info.key(), meta.writable, meta.signer)
+    });
+    let account_infos: [&AccountInfo; N] = core::array::from_fn(|idx| accounts[idx].account_info);
+
+    check_signer_seeds(&accounts)?;
+
+    for meta in accounts.iter_mut() {
+        meta.release();
+    }
+
+    let result = with_cpi_signers(&accounts, |signers| {
+        invoke_signed(
+            &Instruction {
+                program_id,
+                accounts: &metas,
+                data,
+            },
+            &account_infos,
+            signers,
+        )
+    });
+
+    // every account is relocked even if one of them fails, so none is left
+    // released
+    let mut relocked = Ok(());
+    for meta in accounts.iter_mut() {
+        relocked = relocked.and(meta.relock());
+    }
+
+    result.and(relocked)
+}
+
+/// Invokes `program_id` with `data` like [`invoke`], passing `remaining`
+/// after `accounts`, as writable if `remaining_writable`.
+///
+/// The remaining accounts never sign.
+pub fn invoke_with_remaining<'info, const N: usize>(
+    program_id: &Pubkey,
+    mut accounts: [CpiMeta<'_, 'info>; N],
+    remaining: &mut [impl CpiAccount<'info>],
+    remaining_writable: bool,
+    data: &[u8],
+) -> ProgramResult {
+    let len = N + remaining.len();
+    if len > MAX_CPI_ACCOUNTS {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mut metas = [const { MaybeUninit::<AccountMeta>::uninit() }; MAX_CPI_ACCOUNTS];
+    let mut account_infos = [const { MaybeUninit::<&AccountInfo>::uninit() }; MAX_CPI_ACCOUNTS];
+    let fixed = accounts
+        .iter()
+        .map(|meta| (meta.account_info, meta.writable, meta.signer));
+    let remaining_infos = remaining
+        .iter()
+        .map(|account| (account.account_info(), remaining_writable, false));
+    for (idx, (account_info, writable, signer)) in fixed.chain(remaining_infos).enumerate() {
+        metas[idx].write(AccountMeta::new(account_info.key(), writable, signer));
+        account_infos[idx].write(account_info);
+    }
+    // SAFETY: the first `len` metas and account infos were initialized above
+    let (metas, account_infos) = unsafe {
+        (
+            slice::from_raw_parts(metas.as_ptr() as *const AccountMeta, len),
+            slice::from_raw_parts(account_infos.as_ptr() as *const &AccountInfo, len),
+        )
+    };
+
+    check_signer_seeds(&accounts)?;
+
+    for meta in accounts.iter_mut() {
+        meta.release();
+    }
+    for account in remaining.iter_mut() {
+        account.release();
+    }
+
+    let result = with_cpi_signers(&accounts, |signers| {
+        slice_invoke_signed(
+            &Instruction {
+                program_id,
+                accounts: metas,
+                data,
+            },
+            account_infos,
+            signers,
+        )
+    });
+
+    // every account is relocked even if one of them fails, so none is left
+    // released
+    let mut relocked = Ok(());
+    for meta in accounts.iter_mut() {
+        relocked = relocked.and(meta.relock());
+    }
+    for account in remaining.iter_mut() {
+        relocked = relocked.and(account.relock());
+    }
+
+    result.and(relocked)
+}
+
+fn check_signer_seeds(accounts: &[CpiMeta]) -> ProgramResult {
+    let too_many_seeds = accounts.iter().any(|meta| {
+        meta.signer_seeds()
+            .is_some_and(|signer_seeds| signer_seeds.seeds.len() >= MAX_SEEDS)
+    });
+    if too_many_seeds {
+        return Err(ProgramError::MaxSeedLengthExceeded);
+    }
+    Ok(())
+}
+
+/// Calls `f` with the signers of every [`PdaSigner`] in `accounts`, whose
+/// seeds were checked by [`check_signer_seeds`].
+fn with_cpi_signers<const N: usize, R>(
+    accounts: &[CpiMeta; N],
+    f: impl FnOnce(&[Signer]) -> R,
+) -> R {
+    let mut seeds = [const { [const { MaybeUninit::<Seed>::uninit() }; MAX_SEEDS] }; N];
+    let mut signers = [const { MaybeUninit::<Signer>::uninit() }; N];
+    let mut signers_len = 0;
+    for (meta, seeds) in accounts.iter().zip(seeds.iter_mut()) {
+        let Some(signer_seeds) = meta.signer_seeds() else {
+            continue;
+        };
+        for (seed, bytes) in seeds.iter_mut().zip(signer_seeds.seeds) {
+            seed.write(Seed::from(*bytes));
+        }
+        seeds[signer_seeds.seeds.len()].write(Seed::from(signer_seeds.bump));
+        // SAFETY: the first `seeds.len() + 1` seeds were initialized above
+        let seeds = unsafe {
+            slice::from_raw_parts(seeds.as_ptr() as *const Seed, signer_seeds.seeds.len() + 1)
+        };
+        signers[signers_len].write(Signer::from(seeds));
+        signers_len += 1;
+    }
+    // SAFETY: the first `signers_len` signers were initialized above
+    let signers = unsafe { slice::from_raw_parts(signers.as_ptr() as *const Signer, signers_len) };
+    f(signers)
+}
+
+/// Calls `f` with the signer of the program derived address of `seeds` and
+/// `bump`.
+pub(crate) fn with_signer<R>(
+    seeds: &[&[u8]],
+    bump: &[u8; 1],
+    f: impl FnOnce(&[Signer]) -> R,
+) -> Result<R, ProgramError> {
+    if seeds.len() >= MAX_SEEDS {
+        return Err(ProgramError::MaxSeedLengthExceeded);
+    }
+    let mut signer_seeds = [const { MaybeUninit::<Seed>::uninit() }; MAX_SEEDS];
+    for (seed, bytes) in signer_seeds.iter_mut().zip(seeds) {
+        seed.write(Seed::from(*bytes));
+    }
+    signer_seeds[seeds.len()].write(Seed::from(bump));
+    // SAFETY: the first `seeds.len() + 1` seeds were initialized above
+    let signer_seeds =
+        unsafe { slice::from_raw_parts(signer_seeds.as_ptr() as *const Seed, seeds.len() + 1) };
+    Ok(f(&[Signer::from(signer_seeds)]))
+}
+
+impl<'info, T, P, S> CpiAccount<'info> for Account<'info, T, P, S> {
+    fn account_info(&self) -> &'info AccountInfo {
+        self.account_info.raw()
+    }
+
+    fn release(&mut self) {
+        self.account_info.release();
+    }
+
+    fn relock(&mut self) -> Result<(), ProgramError> {
+        self.account_info.relock()
+    }
+}
+
+impl<'info, T, P, S> CpiWritable<'info> for Account<'info, T, P, S> where P: AccountWrite {}
+
+impl<'info, T, P> CpiSigner<'info> for Account<'info, T, P, Signed> {}
+
+impl<'info, T, P, const N: usize> CpiAccount<'info> for PdaSigner<'_, 'info, T, P, N> {
+    fn account_info(&self) -> &'info AccountInfo {
+        CpiAccount::account_info(&*self.account)
+    }
+
+    fn release(&mut self) {
+        self.account.release();
+    }
+
+    fn relock(&mut self) -> Result<(), ProgramError> {
+        self.account.relock()
+    }
+
+    fn signer_seeds(&self) -> Option<SignerSeeds<'_>> {
+        Some(SignerSeeds {
+            seeds: &self.seeds,
+            bump: &self.bump,
+        })
+    }
+}
+
+impl<'info, T, P, const N: usize> CpiWritable<'info> for PdaSigner<'_, 'info, T, P, N> where
+    P: AccountWrite
+{
+}
+
+impl<'info, T, P, const N: usize> CpiSigner<'info> for PdaSigner<'_, 'info, T, P, N> {}
+
+impl<'info, P> CpiAccount<'info> for Program<'info, P> {
+    fn account_info(&self) -> &'info AccountInfo {
+        Program::account_info(self)
+    }
+
+    fn release(&mut self) {}
+
+    fn relock(&mut self) -> Result<(), ProgramError> {
+        Ok(())
+    }
+}
+
+impl<'info, T> CpiAccount<'info> for Sysvar<'info, T> {
+    fn account_info(&self) -> &'info AccountInfo {
+        Sysvar::account_info(self)
+    }
+
+    fn release(&mut self) {}
+
+    fn relock(&mut self) -> Result<(), ProgramError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_args() {
+        let mut data = [0; 9];
+        assert_eq!(serialize_args(&mut data, &(7u64, true)), Ok(()));
+        assert_eq!(data, [7, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let mut data = [0; 10];
+        assert_eq!(
+            serialize_args(&mut data, &(7u64, true)),
+            Err(ProgramError::InvalidInstructionData)
+        );
+
+        let mut data = [0; 16];
+        assert_eq!(serialize_variable_args(&mut data, &(1u8, "abc")), Ok(8));
+        assert_eq!(data[..8], [1, 3, 0, 0, 0, b'a', b'b', b'c']);
+
+        let mut data = [0; 4];
+        assert_eq!(
+            serialize_variable_args(&mut data, &(1u8, "abc")),
+            Err(ProgramError::BorshIoError)
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! The program entrypoint.
+//!
+//! [`program!`](crate::program) declares the entrypoint of a program from its
+//! contract and dispatcher. The pinocchio entrypoint it expands to is picked
+//! by the `sol_ez_entrypoint` cfg, which sol-gen sets from the `entrypoint`
+//! of the `[program]` in contract.toml:
+//!
+//! - `standard`: parses every account before dispatching.
+//! - `lazy`: hands the unparsed input to [`Contract::dispatch_lazy`], which
+//!   generated dispatchers implement by reading the instruction first, with
+//!   [`peek_instruction`], then only parsing the accounts it declares, with
+//!   its remaining accounts parsed as they are iterated.
+//! - `no_allocator`: the standard entrypoint without a heap allocator.
+//!
+//! The entrypoint is only declared with the `bpf` feature of the program, so
+//! the program can still be linked into tests.
+
+use core::{mem::MaybeUninit, ptr, slice};
+
+use pinocchio::{
+    account_info::AccountInfo,
+    entrypoint::{InstructionContext, MaybeAccount},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult, MAX_TX_ACCOUNTS,
+};
+
+use crate::Contract;
+
+/// Declares the entrypoint of the program, dispatching instructions to the
+/// contract `$program` through the generated `$dispatcher`.
+///
+/// ```ignore
+/// sol_ez::program!(MyClaim => ClaimDispatcher);
+/// ```
+#[macro_export]
+macro_rules! program {
+    ($program:ty => $($dispatcher:ident)::+) => {
+        #[cfg(feature = "bpf")]
+        mod __sol_ez_program {
+            use super::*;
+
+            #[cfg(not(sol_ez_entrypoint = "lazy"))]
+            fn process_instruction(
+                program_id: &$crate::__private::pinocchio::pubkey::Pubkey,
+                accounts: &[$crate::__private::pinocchio::account_info::AccountInfo],
+                payload: &[u8],
+            ) -> $crate::__private::pinocchio::ProgramResult {
+                <$($dispatcher)::+<$program> as $crate::Contract>::dispatch(
+                    program_id, accounts, payload,
+                )
+            }
+
+            #[cfg(sol_ez_entrypoint = "lazy")]
+            fn process_instruction(
+                context: $crate::__private::pinocchio::entrypoint::InstructionContext,
+            ) -> $crate::__private::pinocchio::ProgramResult {
+                <$($dispatcher)::+<$program> as $crate::Contract>::dispatch_lazy(context)
+            }
+
+            #[cfg(not(sol_ez_entrypoint = "lazy"))]
+            $crate::__private::pinocchio::program_entrypoint!(process_instruction);
+            #[cfg(sol_ez_entrypoint = "lazy")]
+            $crate::__private::pinocchio::lazy_program_entrypoint!(process_instruction);
+
+            #[cfg(not(sol_ez_entrypoint = "no_allocator"))]
+            $crate::__private::pinocchio::default_allocator!();
+            #[cfg(sol_ez_entrypoint = "no_allocator")]
+            $crate::__private::pinocchio::no_allocator!();
+
+            $crate::__private::pinocchio::default_panic_handler!();
+        }
+    };
+}
+
+/// Reads the program id and data of the instruction in `context` without
+/// parsing its accounts.
+///
+/// The accounts are skipped on a copy of `context`, so they can still be
+/// parsed from `context` once the instruction is known. Like the accounts of
+/// `context`, both are borrowed from the input of the program, which outlives
+/// the instruction.
+pub fn peek_instruction(
+    context: &InstructionContext,
+) -> Result<(&'static Pubkey, &'static [u8]), ProgramError> {
+    // SAFETY: the context is only a cursor into the input, which outlives it
+    let mut cursor = unsafe { ptr::read(context) };
+    for _ in 0..cursor.remaining() {
+        cursor.next_account()?;
+    }
+    let program_id: *const Pubkey = cursor.program_id()?;
+    let data = cursor.instruction_data()?;
+    // SAFETY: both point into the input rather than into the cursor
+    Ok(unsafe {
+        (
+            &*program_id,
+            slice::from_raw_parts(data.as_ptr(), data.len()),
+        )
+    })
+}
+
+/// Parses the accounts of the instruction in `context` into `accounts`, until
+/// either runs out.
+///
+/// The accounts left in `context` are not parsed.
+pub fn parse_accounts<'a>(
+    context: &mut InstructionContext,
+    accounts: &'a mut [MaybeUninit<AccountInfo>],
+) -> Result<&'a [AccountInfo], ProgramError> {
+    let len = (context.remaining() as usize).min(accounts.len());
+    for idx in 0..len {
+        let account = match context.next_account()? {
+            MaybeAccount::Account(account) => account,
+            // duplicates always refer to an earlier account
+            MaybeAccount::Duplicated(original) => unsafe {
+                accounts[original as usize].assume_init_ref().clone()
+            },
+        };
+        accounts[idx].write(account);
+    }
+    // SAFETY: the first `len` accounts were initialized above
+    Ok(unsafe { slice::from_raw_parts(accounts.as_ptr() as *const AccountInfo, len) })
+}
+
+/// Parses up to `N` accounts of the instruction in `context` and dispatches
+/// it to `C`.
+///
+/// Accounts past the first `N` are skipped without being parsed, so `N` only
+/// needs to cover the accounts the instructions of `C` declare. Account data
+/// is not touched until the dispatcher matched the instruction and loads its
+/// accounts.
+pub fn dispatch_parsed<C, const N: usize>(mut context: InstructionContext) -> ProgramResult
+where
+    C: Contract + ?Sized,
+{
+    if context.remaining() as usize > MAX_TX_ACCOUNTS {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    const UNINIT: MaybeUninit<AccountInfo> = MaybeUninit::uninit();
+    let mut accounts = [UNINIT; N];
+    let accounts = parse_accounts(&mut context, &mut accounts)?;
+    for _ in 0..context.remaining() {
+        context.next_account()?;
+    }
+
+    C::dispatch(context.program_id()?, accounts, context.instruction_data()?)
+}
@@ -0,0 +1,34 @@
+//! Errors raised by the framework itself.
+
+use pinocchio::program_error::ProgramError;
+
+/// A framework error, returned as a [`ProgramError::Custom`] code.
+///
+/// Codes start at 3000, leaving the codes below free for programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ErrorCode {
+    /// The account was closed earlier in the transaction.
+    AccountClosed = 3000,
+    /// A `has_one` field of the account does not match the key of its
+    /// sibling account.
+    ConstraintHasOne = 3001,
+    /// The account is not at its declared address.
+    ConstraintAddress = 3002,
+    /// A `constraint` expression of the account did not hold.
+    Constraint = 3003,
+    /// A mutable account was also passed as another account of the
+    /// instruction.
+    DuplicateAccount = 3004,
+    /// No signature verification precompile instruction of the transaction
+    /// verified the expected signature.
+    SignatureNotVerified = 3005,
+    /// The invoked program set no return data.
+    MissingReturnData = 3006,
+}
+
+impl From<ErrorCode> for ProgramError {
+    fn from(value: ErrorCode) -> Self {
+        ProgramError::Custom(value as u32)
+    }
+}
@@ -7,14 +7,26 @@ extern crate std;
 
 use core::{mem::MaybeUninit, ptr};
 
-use pinocchio::{account_info::AccountInfo, pubkey::Pubkey, ProgramResult};
+use pinocchio::{
+    account_info::AccountInfo, entrypoint::InstructionContext, pubkey::Pubkey, ProgramResult,
+    MAX_TX_ACCOUNTS,
+};
 
 pub use account::{AccountData, AccountDataConfig, DataSize};
 pub use instruction_data::InstructionData;
+pub use pinocchio_system::ID as SYSTEM_PROGRAM_ID;
+pub use transfer::transfer;
 
 pub mod account;
 pub mod account_info;
+pub mod cpi;
+pub mod entrypoint;
+pub mod error;
 pub mod instruction_data;
+pub mod math;
+pub mod precompile;
+pub mod token;
+pub mod transfer;
 
 pub trait Contract {
     fn dispatch<'info>(
@@ -22,6 +34,19 @@ pub trait Contract {
         accounts: &'info [AccountInfo],
         payload: &[u8],
     ) -> ProgramResult;
+
+    /// Dispatches an instruction from the lazy entrypoint.
+    ///
+    /// Parses every account up front and calls [`Contract::dispatch`] by
+    /// default.
+    fn dispatch_lazy(context: InstructionContext) -> ProgramResult {
+        entrypoint::dispatch_parsed::<Self, MAX_TX_ACCOUNTS>(context)
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use pinocchio;
 }
 
 pub trait Seed<const D: usize, const N: usize> {
@@ -0,0 +1,89 @@
+//! Checked arithmetic on account fields.
+//!
+//! Plain arithmetic on the fields of an account panics on overflow in debug
+//! builds and wraps in release builds, either aborting the instruction
+//! without a useful error or silently corrupting the account. The operations
+//! of [`CheckedField`] leave the field unchanged and return
+//! [`ProgramError::ArithmeticOverflow`] instead.
+//!
+//! ```ignore
+//! use sol_ez::math::CheckedField;
+//!
+//! accounts.claim.as_ref_mut().amount_acquired.checked_add_field(amount)?;
+//! ```
+
+use pinocchio::program_error::ProgramError;
+
+/// A numeric field updated in place with checked arithmetic.
+pub trait CheckedField: Sized {
+    /// Adds `rhs` to the field.
+    fn checked_add_field(&mut self, rhs: Self) -> Result<(), ProgramError>;
+
+    /// Subtracts `rhs` from the field.
+    fn checked_sub_field(&mut self, rhs: Self) -> Result<(), ProgramError>;
+
+    /// Multiplies the field by `rhs`.
+    fn checked_mul_field(&mut self, rhs: Self) -> Result<(), ProgramError>;
+}
+
+macro_rules! impl_checked_field {
+    ($($ty:ty),+) => {
+        $(
+            impl CheckedField for $ty {
+                #[inline(always)]
+                fn checked_add_field(&mut self, rhs: Self) -> Result<(), ProgramError> {
+                    *self = self.checked_add(rhs).ok_or(ProgramError::ArithmeticOverflow)?;
+                    Ok(())
+                }
+
+                #[inline(always)]
+                fn checked_sub_field(&mut self, rhs: Self) -> Result<(), ProgramError> {
+                    *self = self.checked_sub(rhs).ok_or(ProgramError::ArithmeticOverflow)?;
+                    Ok(())
+                }
+
+                #[inline(always)]
+                fn checked_mul_field(&mut self, rhs: Self) -> Result<(), ProgramError> {
+                    *self = self.checked_mul(rhs).ok_or(ProgramError::ArithmeticOverflow)?;
+                    Ok(())
+                }
+            }
+        )+
+    };
+}
+
+impl_checked_field!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_field() {
+        let mut amount = u64::MAX - 1;
+        assert_eq!(amount.checked_add_field(1), Ok(()));
+        assert_eq!(amount, u64::MAX);
+        assert_eq!(
+            amount.checked_add_field(1),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+        assert_eq!(amount, u64::MAX);
+
+        let mut amount = 1u8;
+        assert_eq!(
+            amount.checked_sub_field(2),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+        assert_eq!(amount, 1);
+        assert_eq!(amount.checked_mul_field(200), Ok(()));
+        assert_eq!(
+            amount.checked_mul_field(2),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+        assert_eq!(amount, 200);
+
+        let mut delta = -1i32;
+        assert_eq!(delta.checked_sub_field(i32::MAX), Ok(()));
+        assert_eq!(delta, i32::MIN);
+    }
+}
@@ -0,0 +1,277 @@
+//! Checks against the signature verification precompiles.
+//!
+//! The ed25519 and secp256k1 precompiles verify the signatures listed in their
+//! instruction data, failing the whole transaction when one does not verify.
+//! A program can require an off-chain signature by looking up such an
+//! instruction in the same transaction through the instructions sysvar, and
+//! checking that it covers the expected signer and message.
+
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    account::{Instructions, Sysvar},
+    error::ErrorCode,
+};
+
+/// The address of the ed25519 signature verification precompile.
+pub const ED25519_PROGRAM_ID: Pubkey = [
+    3, 125, 70, 214, 124, 147, 251, 190, 18, 249, 66, 143, 131, 141, 64, 255, 5, 112, 116, 73, 39,
+    244, 138, 100, 252, 202, 112, 68, 128, 0, 0, 0,
+];
+
+/// The address of the secp256k1 signature verification precompile.
+pub const SECP256K1_PROGRAM_ID: Pubkey = [
+    4, 198, 252, 32, 240, 80, 204, 240, 85, 132, 215, 33, 28, 159, 140, 245, 158, 193, 71, 133,
+    187, 22, 106, 30, 40, 48, 232, 18, 32, 0, 0, 0,
+];
+
+/// The size of the offsets of an ed25519 signature.
+const ED25519_OFFSETS_SIZE: usize = 14;
+
+/// The size of the offsets of a secp256k1 signature.
+const SECP256K1_OFFSETS_SIZE: usize = 11;
+
+/// The instruction index of ed25519 offsets into the precompile instruction
+/// itself.
+const ED25519_CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// A signature verified by an ed25519 precompile instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ed25519Signature<'a> {
+    pub pubkey: &'a Pubkey,
+    pub signature: &'a [u8; 64],
+    pub message: &'a [u8],
+}
+
+impl<'a> Ed25519Signature<'a> {
+    /// Parses the signatures of the ed25519 precompile instruction data
+    /// `data`.
+    ///
+    /// Only signatures whose pubkey, signature and message are all held by
+    /// the precompile instruction itself are supported, others are yielded
+    /// as an error.
+    pub fn parse(data: &'a [u8]) -> impl Iterator<Item = Result<Self, ProgramError>> + 'a {
+        let count = data.first().copied().unwrap_or_default() as usize;
+        (0..count).map(move |idx| {
+            let offsets = 2 + idx * ED25519_OFFSETS_SIZE;
+            let offsets = data
+                .get(offsets..offsets + ED25519_OFFSETS_SIZE)
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            let u16_at = |idx: usize| u16::from_le_bytes([offsets[idx * 2], offsets[idx * 2 + 1]]);
+            if [u16_at(1), u16_at(3), u16_at(6)]
+                .into_iter()
+                .any(|index| index != ED25519_CURRENT_INSTRUCTION)
+            {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            Ok(Ed25519Signature {
+                signature: slice_at(data, u16_at(0), 64)?
+                    .try_into()
+                    .map_err(|_| ProgramError::InvalidInstructionData)?,
+                pubkey: slice_at(data, u16_at(2), 32)?
+                    .try_into()
+                    .map_err(|_| ProgramError::InvalidInstructionData)?,
+                message: slice_at(data, u16_at(4), u16_at(5) as usize)?,
+            })
+        })
+    }
+}
+
+/// A signature verified by a secp256k1 precompile instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Secp256k1Signature<'a> {
+    /// The ethereum address of the signer.
+    pub eth_address: &'a [u8; 20],
+    /// The signature followed by its recovery id.
+    pub signature: &'a [u8; 65],
+    pub message: &'a [u8],
+}
+
+impl<'a> Secp256k1Signature<'a> {
+    /// Parses the signatures of the secp256k1 precompile instruction data
+    /// `data`, the instruction at `index` of the transaction.
+    ///
+    /// Only signatures whose address, signature and message are all held by
+    /// the precompile instruction itself are supported, others are yielded
+    /// as an error.
+    pub fn parse(
+        data: &'a [u8],
+        index: usize,
+    ) -> impl Iterator<Item = Result<Self, ProgramError>> + 'a {
+        let count = data.first().copied().unwrap_or_default() as usize;
+        (0..count).map(move |idx| {
+            let offsets = 1 + idx * SECP256K1_OFFSETS_SIZE;
+            let offsets = data
+                .get(offsets..offsets + SECP256K1_OFFSETS_SIZE)
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            let u16_at = |idx: usize| u16::from_le_bytes([offsets[idx], offsets[idx + 1]]);
+            if [offsets[2], offsets[5], offsets[10]]
+                .into_iter()
+                .any(|ix_index| ix_index as usize != index)
+            {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            Ok(Secp256k1Signature {
+                signature: slice_at(data, u16_at(0), 65)?
+                    .try_into()
+                    .map_err(|_| ProgramError::InvalidInstructionData)?,
+                eth_address: slice_at(data, u16_at(3), 20)?
+                    .try_into()
+                    .map_err(|_| ProgramError::InvalidInstructionData)?,
+                message: slice_at(data, u16_at(6), u16_at(8) as usize)?,
+            })
+        })
+    }
+}
+
+fn slice_at(data: &[u8], offset: u16, len: usize) -> Result<&[u8], ProgramError> {
+    let offset = offset as usize;
+    data.get(offset..offset + len)
+        .ok_or(ProgramError::InvalidInstructionData)
+}
+
+/// Checks that an ed25519 precompile instruction of the transaction verified
+/// a signature of `message` by `pubkey`.
+pub fn verify_ed25519(
+    instructions: &Sysvar<'_, Instructions>,
+    pubkey: &Pubkey,
+    message: &[u8],
+) -> Result<(), ProgramError> {
+    let instructions = instructions.instructions()?;
+    for index in 0..instructions.num_instructions() {
+        let ix = instructions.load_instruction_at(index)?;
+        if ix.get_program_id() != &ED25519_PROGRAM_ID {
+            continue;
+        }
+        if Ed25519Signature::parse(ix.get_instruction_data())
+            .flatten()
+            .any(|signature| signature.pubkey == pubkey && signature.message == message)
+        {
+            return Ok(());
+        }
+    }
+    Err(ErrorCode::SignatureNotVerified.into())
+}
+
+/// Checks that a secp256k1 precompile instruction of the transaction verified
+/// a signature of `message` by the holder of `eth_address`.
+pub fn verify_secp256k1(
+    instructions: &Sysvar<'_, Instructions>,
+    eth_address: &[u8; 20],
+    message: &[u8],
+) -> Result<(), ProgramError> {
+    let instructions = instructions.instructions()?;
+    for index in 0..instructions.num_instructions() {
+        let ix = instructions.load_instruction_at(index)?;
+        if ix.get_program_id() != &SECP256K1_PROGRAM_ID {
+            continue;
+        }
+        if Secp256k1Signature::parse(ix.get_instruction_data(), index)
+            .flatten()
+            .any(|signature| signature.eth_address == eth_address && signature.message == message)
+        {
+            return Ok(());
+        }
+    }
+    Err(ErrorCode::SignatureNotVerified.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lays out an ed25519 precompile instruction verifying one signature.
+    fn ed25519_ix(pubkey: &Pubkey, signature: &[u8; 64], message: &[u8]) -> [u8; 128] {
+        let mut data = [0; 128];
+        let start = 2 + ED25519_OFFSETS_SIZE;
+        let (pubkey_offset, signature_offset) = (start, start + 32);
+        let message_offset = signature_offset + 64;
+        data[0] = 1;
+        for (idx, value) in [
+            signature_offset,
+            u16::MAX as usize,
+            pubkey_offset,
+            u16::MAX as usize,
+            message_offset,
+            message.len(),
+            u16::MAX as usize,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            data[2 + idx * 2..4 + idx * 2].copy_from_slice(&(value as u16).to_le_bytes());
+        }
+        data[pubkey_offset..signature_offset].copy_from_slice(pubkey);
+        data[signature_offset..message_offset].copy_from_slice(signature);
+        data[message_offset..message_offset + message.len()].copy_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn test_parse_ed25519() {
+        let data = ed25519_ix(&[1; 32], &[2; 64], b"voucher");
+        let mut signatures = Ed25519Signature::parse(&data);
+        assert_eq!(
+            signatures.next(),
+            Some(Ok(Ed25519Signature {
+                pubkey: &[1; 32],
+                signature: &[2; 64],
+                message: b"voucher",
+            }))
+        );
+        assert_eq!(signatures.next(), None);
+
+        // offsets into another instruction
+        let mut data = data;
+        data[4..6].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            Ed25519Signature::parse(&data).next(),
+            Some(Err(ProgramError::InvalidInstructionData))
+        );
+
+        // offsets past the end of the data
+        let mut data = ed25519_ix(&[1; 32], &[2; 64], b"voucher");
+        data[12..14].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert_eq!(
+            Ed25519Signature::parse(&data).next(),
+            Some(Err(ProgramError::InvalidInstructionData))
+        );
+    }
+
+    #[test]
+    fn test_parse_secp256k1() {
+        let message = b"voucher";
+        let mut data = [0; 128];
+        let start = 1 + SECP256K1_OFFSETS_SIZE;
+        let (address_offset, signature_offset) = (start, start + 20);
+        let message_offset = signature_offset + 65;
+        data[0] = 1;
+        data[1..3].copy_from_slice(&(signature_offset as u16).to_le_bytes());
+        data[3] = 2;
+        data[4..6].copy_from_slice(&(address_offset as u16).to_le_bytes());
+        data[6] = 2;
+        data[7..9].copy_from_slice(&(message_offset as u16).to_le_bytes());
+        data[9..11].copy_from_slice(&(message.len() as u16).to_le_bytes());
+        data[11] = 2;
+        data[address_offset..signature_offset].copy_from_slice(&[1; 20]);
+        data[signature_offset..message_offset].copy_from_slice(&[2; 65]);
+        data[message_offset..message_offset + message.len()].copy_from_slice(message);
+
+        let mut signatures = Secp256k1Signature::parse(&data, 2);
+        assert_eq!(
+            signatures.next(),
+            Some(Ok(Secp256k1Signature {
+                eth_address: &[1; 20],
+                signature: &[2; 65],
+                message,
+            }))
+        );
+        assert_eq!(signatures.next(), None);
+
+        // offsets into another instruction
+        assert_eq!(
+            Secp256k1Signature::parse(&data, 1).next(),
+            Some(Err(ProgramError::InvalidInstructionData))
+        );
+    }
+}
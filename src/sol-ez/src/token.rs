@@ -0,0 +1,558 @@
+//! SPL Token and Token-2022 accounts and instructions.
+//!
+//! [`TokenAccount`] and [`Mint`] are account payloads loaded through
+//! [`AccountBuilder::token_payload`](crate::account::AccountBuilder::token_payload).
+//! Accounts owned by either token program are accepted, and the CPI helpers
+//! invoke the program that owns the token account.
+//!
+//! Associated token accounts are checked against their derived address with
+//! [`check_associated_token_address`], and created on first use with
+//! [`init_associated_token_account`], or with [`AssociatedTokenAccount`] to
+//! check the address before creating it.
+
+use core::marker::PhantomData;
+
+use pinocchio::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+
+use crate::{
+    account::{
+        Account, AccountBuilder, AccountWritable, AssociatedToken, Program, ProgramId, System,
+        Token, Token2022, TokenProgram,
+    },
+    account_info::{AccountRead, Mutable},
+    cpi::{self, CpiAccount, CpiMeta, CpiSigner, CpiWritable},
+};
+
+const MINT_TO: u8 = 7;
+const BURN: u8 = 8;
+const CLOSE_ACCOUNT: u8 = 9;
+const TRANSFER_CHECKED: u8 = 12;
+
+/// The associated token program instruction creating an account unless it
+/// already exists.
+const CREATE_IDEMPOTENT: u8 = 1;
+
+/// Token-2022 stores the account type after the base account length when an
+/// account has extensions.
+const ACCOUNT_TYPE_OFFSET: usize = TokenAccount::LEN;
+
+/// The payload of an account owned by a token program.
+pub trait TokenData: Sized {
+    /// The length of the base account, without Token-2022 extensions.
+    const LEN: usize;
+    /// The Token-2022 account type of the payload.
+    const ACCOUNT_TYPE: u8;
+
+    /// Unpacks the base account, failing if it is not initialized.
+    fn unpack(data: &[u8]) -> Result<Self, ProgramError>;
+}
+
+/// An SPL token account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenAccount {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub delegate: Option<Pubkey>,
+    pub state: TokenAccountState,
+    pub is_native: Option<u64>,
+    pub delegated_amount: u64,
+    pub close_authority: Option<Pubkey>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenAccountState {
+    Initialized,
+    Frozen,
+}
+
+/// An SPL token mint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mint {
+    pub mint_authority: Option<Pubkey>,
+    pub supply: u64,
+    pub decimals: u8,
+    pub freeze_authority: Option<Pubkey>,
+}
+
+impl TokenData for TokenAccount {
+    const LEN: usize = 165;
+    const ACCOUNT_TYPE: u8 = 2;
+
+    fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut reader = Reader(data);
+        let mint = reader.pubkey()?;
+        let owner = reader.pubkey()?;
+        let amount = reader.u64()?;
+        let delegate = reader.option(Reader::pubkey)?;
+        let state = match reader.u8()? {
+            0 => return Err(ProgramError::UninitializedAccount),
+            1 => TokenAccountState::Initialized,
+            2 => TokenAccountState::Frozen,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(TokenAccount {
+            mint,
+            owner,
+            amount,
+            delegate,
+            state,
+            is_native: reader.option(Reader::u64)?,
+            delegated_amount: reader.u64()?,
+            close_authority: reader.option(Reader::pubkey)?,
+        })
+    }
+}
+
+impl TokenData for Mint {
+    const LEN: usize = 82;
+    const ACCOUNT_TYPE: u8 = 1;
+
+    fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut reader = Reader(data);
+        let mint_authority = reader.option(Reader::pubkey)?;
+        let supply = reader.u64()?;
+        let decimals = reader.u8()?;
+        if reader.u8()? != 1 {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(Mint {
+            mint_authority,
+            supply,
+            decimals,
+            freeze_authority: reader.option(Reader::pubkey)?,
+        })
+    }
+}
+
+/// Checks that `owner` is a token program and `data` holds a `T`, then
+/// unpacks it.
+pub(crate) fn unpack_owned<T>(owner: &Pubkey, data: &[u8]) -> Result<T, ProgramError>
+where
+    T: TokenData,
+{
+    if owner != &Token::ID && owner != &Token2022::ID {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    match data.len() {
+        len if len == T::LEN => {}
+        len if len > ACCOUNT_TYPE_OFFSET
+            && owner == &Token2022::ID
+            && data[ACCOUNT_TYPE_OFFSET] == T::ACCOUNT_TYPE => {}
+        _ => return Err(ProgramError::InvalidAccountData),
+    }
+    T::unpack(data)
+}
+
+impl<'info, T, P, S> Account<'info, T, P, S>
+where
+    T: TokenData,
+{
+    #[allow(clippy::should_implement_trait)]
+    pub fn as_ref(&self) -> &T
+    where
+        P: AccountRead,
+    {
+        &self.inner
+    }
+
+    /// Unpacks the account again, picking up changes made by a CPI.
+    pub fn reload(&mut self) -> Result<(), ProgramError>
+    where
+        P: AccountRead,
+    {
+        self.inner = unpack_owned(self.account_info.owner(), self.account_info.data())?;
+        Ok(())
+    }
+}
+
+/// Transfers `amount` tokens of `mint` from `from` to `to`.
+///
+/// Uses `TransferChecked`, which the token programs check against the mint
+/// and its decimals, and which Token-2022 requires for mints with extensions.
+pub fn transfer<'info, P, S1, S2, S3>(
+    from: &mut Account<'info, TokenAccount, Mutable, S1>,
+    mint: &mut Account<'info, Mint, P, S2>,
+    to: &mut Account<'info, TokenAccount, Mutable, S3>,
+    authority: &mut impl CpiSigner<'info>,
+    amount: u64,
+) -> ProgramResult
+where
+    P: AccountRead,
+{
+    let program_id = *from.owner();
+    let mut data = [0; 10];
+    data[..9].copy_from_slice(&amount_data(TRANSFER_CHECKED, amount));
+    data[9] = mint.as_ref().decimals;
+    cpi::invoke(
+        &program_id,
+        [
+            CpiMeta::writable(from),
+            CpiMeta::readonly(mint),
+            CpiMeta::writable(to),
+            CpiMeta::readonly_signer(authority),
+        ],
+        &data,
+    )?;
+    from.reload()?;
+    to.reload()
+}
+
+/// Mints `amount` new tokens of `mint` to `to`.
+pub fn mint_to<'info, S1, S2>(
+    mint: &mut Account<'info, Mint, Mutable, S1>,
+    to: &mut Account<'info, TokenAccount, Mutable, S2>,
+    mint_authority: &mut impl CpiSigner<'info>,
+    amount: u64,
+) -> ProgramResult {
+    let program_id = *mint.owner();
+    let data = amount_data(MINT_TO, amount);
+    cpi::invoke(
+        &program_id,
+        [
+            CpiMeta::writable(mint),
+            CpiMeta::writable(to),
+            CpiMeta::readonly_signer(mint_authority),
+        ],
+        &data,
+    )?;
+    mint.reload()?;
+    to.reload()
+}
+
+/// Burns `amount` tokens held by `from`.
+pub fn burn<'info, S1, S2>(
+    from: &mut Account<'info, TokenAccount, Mutable, S1>,
+    mint: &mut Account<'info, Mint, Mutable, S2>,
+    authority: &mut impl CpiSigner<'info>,
+    amount: u64,
+) -> ProgramResult {
+    let program_id = *from.owner();
+    let data = amount_data(BURN, amount);
+    cpi::invoke(
+        &program_id,
+        [
+            CpiMeta::writable(from),
+            CpiMeta::writable(mint),
+            CpiMeta::readonly_signer(authority),
+        ],
+        &data,
+    )?;
+    from.reload()?;
+    mint.reload()
+}
+
+/// Closes the empty token account `account`, sending its lamports to
+/// `destination`.
+pub fn close_account<'info, S>(
+    mut account: Account<'info, TokenAccount, Mutable, S>,
+    destination: &mut impl CpiWritable<'info>,
+    authority: &mut impl CpiSigner<'info>,
+) -> ProgramResult {
+    let program_id = *account.owner();
+    cpi::invoke(
+        &program_id,
+        [
+            CpiMeta::writable(&mut account),
+            CpiMeta::writable(destination),
+            CpiMeta::readonly_signer(authority),
+        ],
+        &[CLOSE_ACCOUNT],
+    )
+}
+
+/// Returns the associated token account address of `owner` for `mint`, and
+/// its bump.
+pub fn find_associated_token_address(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> (Pubkey, u8) {
+    pubkey::find_program_address(&[owner, token_program, mint], &AssociatedToken::ID)
+}
+
+/// Checks that `key` is the associated token account of `owner` for `mint`.
+pub fn check_associated_token_address(
+    key: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> ProgramResult {
+    let (address, _bump) = find_associated_token_address(owner, mint, token_program);
+    if key != &address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
+
+/// An associated token account checked to be at the address derived from its
+/// owner and mint, which may not exist yet.
+///
+/// Generated dispatchers check the address of an `init_if_needed` associated
+/// token account before the other accounts are checked, and only create it
+/// once they were.
+pub struct AssociatedTokenAccount<'info, T> {
+    account: &'info AccountInfo,
+    token_program: PhantomData<T>,
+}
+
+impl<'info, T> AssociatedTokenAccount<'info, T>
+where
+    T: TokenProgram,
+{
+    /// Checks that `account` is the associated token account of `owner` for
+    /// `mint`.
+    pub fn new(
+        account: &'info AccountInfo,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        _token_program: &Program<'info, T>,
+    ) -> Result<Self, ProgramError> {
+        check_associated_token_address(account.key(), owner, mint, &T::ID)?;
+        Ok(AssociatedTokenAccount {
+            account,
+            token_program: PhantomData,
+        })
+    }
+
+    pub fn key(&self) -> &Pubkey {
+        self.account.key()
+    }
+
+    /// Loads the account, first creating it through the associated token
+    /// program if it does not exist yet.
+    ///
+    /// The associated token program is only taken to require that it was
+    /// passed to the instruction, so it can be invoked.
+    pub fn init(
+        self,
+        owner: &mut impl CpiAccount<'info>,
+        mint: &mut impl CpiAccount<'info>,
+        payer: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        system_program: &mut Program<'info, System>,
+        token_program: &mut Program<'info, T>,
+        _associated_token_program: &Program<'info, AssociatedToken>,
+    ) -> Result<AccountWritable<'info, TokenAccount>, ProgramError> {
+        if self.account.data_is_empty() {
+            cpi::invoke(
+                &AssociatedToken::ID,
+                [
+                    CpiMeta::writable_signer(payer),
+                    CpiMeta::writable(&mut Unloaded(self.account)),
+                    CpiMeta::readonly(owner),
+                    CpiMeta::readonly(mint),
+                    CpiMeta::readonly(system_program),
+                    CpiMeta::readonly(token_program),
+                ],
+                &[CREATE_IDEMPOTENT],
+            )?;
+        }
+        AccountBuilder::new(self.account)
+            .token_payload::<TokenAccount>()
+            .mutable()?
+            .build()
+    }
+
+    /// Same as [`init`](Self::init), for the associated token account owned by
+    /// `payer`.
+    pub fn init_for_payer(
+        self,
+        mint: &mut impl CpiAccount<'info>,
+        payer: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+        system_program: &mut Program<'info, System>,
+        token_program: &mut Program<'info, T>,
+        associated_token_program: &Program<'info, AssociatedToken>,
+    ) -> Result<AccountWritable<'info, TokenAccount>, ProgramError> {
+        // the payer is released for the CPI through its own meta
+        let mut owner = Unloaded(payer.account_info());
+        self.init(
+            &mut owner,
+            mint,
+            payer,
+            system_program,
+            token_program,
+            associated_token_program,
+        )
+    }
+}
+
+/// Loads the associated token account of `owner` for `mint`, first creating
+/// it through the associated token program if it does not exist yet.
+#[allow(clippy::too_many_arguments)]
+pub fn init_associated_token_account<'info, T>(
+    account: &'info AccountInfo,
+    owner: &mut impl CpiAccount<'info>,
+    mint: &mut impl CpiAccount<'info>,
+    payer: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+    system_program: &mut Program<'info, System>,
+    token_program: &mut Program<'info, T>,
+    associated_token_program: &Program<'info, AssociatedToken>,
+) -> Result<AccountWritable<'info, TokenAccount>, ProgramError>
+where
+    T: TokenProgram,
+{
+    AssociatedTokenAccount::new(
+        account,
+        owner.account_info().key(),
+        mint.account_info().key(),
+        token_program,
+    )?
+    .init(
+        owner,
+        mint,
+        payer,
+        system_program,
+        token_program,
+        associated_token_program,
+    )
+}
+
+/// Same as [`init_associated_token_account`], for the associated token
+/// account owned by `payer`.
+pub fn init_associated_token_account_for_payer<'info, T>(
+    account: &'info AccountInfo,
+    mint: &mut impl CpiAccount<'info>,
+    payer: &mut (impl CpiWritable<'info> + CpiSigner<'info>),
+    system_program: &mut Program<'info, System>,
+    token_program: &mut Program<'info, T>,
+    associated_token_program: &Program<'info, AssociatedToken>,
+) -> Result<AccountWritable<'info, TokenAccount>, ProgramError>
+where
+    T: TokenProgram,
+{
+    AssociatedTokenAccount::new(
+        account,
+        payer.account_info().key(),
+        mint.account_info().key(),
+        token_program,
+    )?
+    .init_for_payer(
+        mint,
+        payer,
+        system_program,
+        token_program,
+        associated_token_program,
+    )
+}
+
+/// An account without a borrow of its own to release, either because it is
+/// not loaded yet or because it is released through another [`CpiMeta`].
+struct Unloaded<'info>(&'info AccountInfo);
+
+impl<'info> CpiAccount<'info> for Unloaded<'info> {
+    fn account_info(&self) -> &'info AccountInfo {
+        self.0
+    }
+
+    fn release(&mut self) {}
+
+    fn relock(&mut self) -> Result<(), ProgramError> {
+        Ok(())
+    }
+}
+
+impl<'info> CpiWritable<'info> for Unloaded<'info> {}
+
+fn amount_data(instruction: u8, amount: u64) -> [u8; 9] {
+    let mut data = [instruction; 9];
+    data[1..].copy_from_slice(&amount.to_le_bytes());
+    data
+}
+
+/// Reads the little endian, `COption` based layout of the token programs.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ProgramError> {
+        let (bytes, rest) = self
+            .0
+            .split_first_chunk::<N>()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ProgramError> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn u64(&mut self) -> Result<u64, ProgramError> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey, ProgramError> {
+        self.take()
+    }
+
+    fn option<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ProgramError>,
+    ) -> Result<Option<T>, ProgramError> {
+        let tag = u32::from_le_bytes(self.take()?);
+        let value = f(self)?;
+        match tag {
+            0 => Ok(None),
+            1 => Ok(Some(value)),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpack_token_account() {
+        let mut data = [0; TokenAccount::LEN];
+        data[..32].copy_from_slice(&[1; 32]);
+        data[32..64].copy_from_slice(&[2; 32]);
+        data[64..72].copy_from_slice(&100u64.to_le_bytes());
+        data[108] = 1;
+
+        let account = unpack_owned::<TokenAccount>(&Token::ID, &data).unwrap();
+        assert_eq!(account.mint, [1; 32]);
+        assert_eq!(account.owner, [2; 32]);
+        assert_eq!(account.amount, 100);
+        assert_eq!(account.delegate, None);
+        assert_eq!(account.state, TokenAccountState::Initialized);
+
+        assert_eq!(
+            unpack_owned::<TokenAccount>(&[0; 32], &data),
+            Err(ProgramError::InvalidAccountOwner)
+        );
+        assert_eq!(
+            unpack_owned::<Mint>(&Token::ID, &data),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        data[108] = 0;
+        assert_eq!(
+            unpack_owned::<TokenAccount>(&Token::ID, &data),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
+
+    #[test]
+    fn test_unpack_token_2022_mint_with_extensions() {
+        let mut data = [0; 170];
+        data[..4].copy_from_slice(&1u32.to_le_bytes());
+        data[4..36].copy_from_slice(&[3; 32]);
+        data[44] = 6;
+        data[45] = 1;
+        data[ACCOUNT_TYPE_OFFSET] = Mint::ACCOUNT_TYPE;
+
+        let mint = unpack_owned::<Mint>(&Token2022::ID, &data).unwrap();
+        assert_eq!(mint.mint_authority, Some([3; 32]));
+        assert_eq!(mint.decimals, 6);
+        assert_eq!(
+            unpack_owned::<Mint>(&Token::ID, &data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Moving lamports between accounts.
+//!
+//! Only the owner of an account may debit it. Accounts holding data of the
+//! program are debited directly, while accounts without data, such as
+//! wallets, are owned by the system program and debited through it on their
+//! signature. [`transfer`] picks the path from the type of the source, so a
+//! system account that did not sign can't be passed at all.
+
+use pinocchio::{
+    program_error::ProgramError,
+    sysvars::{rent::Rent, Sysvar},
+    ProgramResult,
+};
+use pinocchio_system::instructions::Transfer;
+
+use crate::{
+    account::{Account, AccountData},
+    account_info::{AccountInfo, Empty, Mutable, Signed},
+};
+
+/// An account lamports can be transferred from.
+pub trait LamportSource<'info> {
+    /// Moves `amount` lamports to `to`, leaving this account rent exempt.
+    fn transfer_to<S>(
+        &mut self,
+        to: &mut AccountInfo<'info, Mutable, S>,
+        amount: u64,
+    ) -> ProgramResult;
+}
+
+/// Moves `amount` lamports from `from` to `to`.
+///
+/// Fails with [`ProgramError::InsufficientFunds`] when `from` holds fewer
+/// lamports, and with [`ProgramError::AccountNotRentExempt`] when what is
+/// left would not keep `from` rent exempt. An account without data that is not
+/// owned by the system program fails with [`ProgramError::IllegalOwner`].
+pub fn transfer<'info, F, T, S>(
+    from: &mut F,
+    to: &mut Account<'info, T, Mutable, S>,
+    amount: u64,
+) -> ProgramResult
+where
+    F: LamportSource<'info>,
+{
+    from.transfer_to(&mut to.account_info, amount)
+}
+
+impl<'info, const DISCRIMINATOR_SIZE: usize, T, S> LamportSource<'info>
+    for Account<'info, AccountData<DISCRIMINATOR_SIZE, T>, Mutable, S>
+{
+    fn transfer_to<D>(
+        &mut self,
+        to: &mut AccountInfo<'info, Mutable, D>,
+        amount: u64,
+    ) -> ProgramResult {
+        let left = remaining_lamports(self.account_info.lamports(), amount)?;
+        // a program account is only ever emptied by closing it
+        if left < Rent::get()?.minimum_balance(self.account_info.data().len()) {
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+        self.account_info.set_lamports(left);
+        to.add_lamports(amount)
+    }
+}
+
+impl<'info> LamportSource<'info> for Account<'info, Empty, Mutable, Signed> {
+    fn transfer_to<D>(
+        &mut self,
+        to: &mut AccountInfo<'info, Mutable, D>,
+        amount: u64,
+    ) -> ProgramResult {
+        if self.account_info.owner() != &pinocchio_system::ID {
+            return Err(ProgramError::IllegalOwner);
+        }
+        let left = remaining_lamports(self.account_info.lamports(), amount)?;
+        // a system account may be emptied, but not left below the minimum
+        if left != 0 && left < Rent::get()?.minimum_balance(0) {
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+        self.account_info.while_released(|from| {
+            to.while_released(|to| {
+                Transfer {
+                    from,
+                    to,
+                    lamports: amount,
+                }
+                .invoke()
+            })
+        })
+    }
+}
+
+fn remaining_lamports(lamports: u64, amount: u64) -> Result<u64, ProgramError> {
+    lamports
+        .checked_sub(amount)
+        .ok_or(ProgramError::InsufficientFunds)
+}
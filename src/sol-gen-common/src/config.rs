@@ -1,8 +1,8 @@
-use std::{collections::BTreeMap, str::FromStr};
+use std::{collections::BTreeMap, fmt, str::FromStr};
 
 use serde::Deserialize;
 
-use crate::error::SolGenError;
+use crate::{error::SolGenError, my_idl};
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub struct Config {
@@ -13,11 +13,53 @@ pub struct Config {
     pub accounts: BTreeMap<String, Account>,
     #[serde(default)]
     pub message: BTreeMap<String, Message>,
+    #[serde(default)]
+    pub errors: BTreeMap<String, Error>,
 }
 
+/// The first custom error code used by the errors of `sol_ez` itself.
+const FRAMEWORK_ERROR_CODE: u32 = 3000;
+
 impl Config {
     pub fn validate(&self) -> Result<(), SolGenError> {
+        for (name, error) in self.errors.iter() {
+            if error.code >= FRAMEWORK_ERROR_CODE {
+                Err(anyhow::anyhow!(
+                    "error({}) code {} is reserved for framework errors",
+                    name,
+                    error.code
+                ))?;
+            } else if self
+                .errors
+                .iter()
+                .any(|(other, e)| other != name && e.code == error.code)
+            {
+                Err(anyhow::anyhow!("duplicate code in errors({})", name))?;
+            }
+        }
+
         for (ix_name, ix) in self.ix.iter() {
+            ix.validate_args(ix_name)?;
+
+            if let Some(remaining) = &ix.remaining {
+                if ix.accounts.contains_key("remaining") {
+                    Err(anyhow::anyhow!(
+                        "id({}) account(remaining) is taken by the remaining accounts",
+                        ix_name
+                    ))?;
+                } else if !self.accounts.contains_key(&remaining.r#type) {
+                    Err(anyhow::anyhow!(
+                        "id({}) remaining accounts type {} not defined",
+                        ix_name,
+                        remaining.r#type
+                    ))?;
+                }
+            }
+
+            if let Some(voucher) = &ix.voucher {
+                self.validate_voucher(ix_name, ix, voucher)?;
+            }
+
             let mut idxs = vec![0; ix.accounts.len()];
             for (acc_name, acc) in ix.accounts.iter() {
                 if idxs[acc.id] == 1 {
@@ -26,42 +68,608 @@ impl Config {
                         ix_name,
                         acc_name
                     ))?;
-                } else if acc.create && (acc.mutable | acc.signed) {
+                } else if acc.program.is_some() && acc.sysvar.is_some() {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) cant be both a program and a sysvar",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if (acc.program.is_some() || acc.sysvar.is_some())
+                    && (acc.create.is_some()
+                        || acc.mutable
+                        || acc.signed
+                        || acc.r#type.is_some()
+                        || acc.owner.is_some())
+                {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) program and sysvar accounts cant be create, mutable, signed or have a type or owner",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if acc.create.is_some() && acc.owner.is_some() {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) cant be create and have an owner",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if acc.create.is_some() && (acc.mutable | acc.signed) {
+                    // keypair accounts are signed by their kind
                     Err(anyhow::anyhow!(
                         "id({}) account({}) cant be create and mutable or signed",
                         ix_name,
                         acc_name
                     ))?;
+                } else if acc.init_if_needed
+                    && (acc.create.is_some()
+                        || acc.mutable
+                        || acc.signed
+                        || acc.owner.is_some()
+                        || acc.token_kind().is_some())
+                {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) init_if_needed accounts cant be create, mutable, signed, a token account or have an owner",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if acc.init_if_needed && acc.r#type.is_none() {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) init_if_needed accounts must have a type",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if acc.close.is_some()
+                    && (!acc.mutable || acc.r#type.is_none() || acc.token_kind().is_some())
+                {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) closed accounts must be mutable and have a non token type",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if let Some(destination) = acc.close.as_ref().filter(|destination| {
+                    *destination == acc_name
+                        || !ix
+                            .accounts
+                            .get(*destination)
+                            .is_some_and(|dest| dest.mutable)
+                }) {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) close destination {} must be another mutable account",
+                        ix_name,
+                        acc_name,
+                        destination
+                    ))?;
+                } else if acc.allow_duplicate
+                    && (acc.create.is_some()
+                        || acc.init_if_needed
+                        || acc.mutable
+                        || acc.program.is_some()
+                        || acc.sysvar.is_some())
+                {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) allow_duplicate accounts must be read only and cant be program or sysvar accounts",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if acc.manual_apply
+                    && (!acc.mutable || acc.r#type.is_none() || acc.token_kind().is_some())
+                {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) manual_apply accounts must be mutable and have a non token type",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if acc.rent_exempt.is_some() && !acc.is_written_program_account() {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) rent_exempt is only allowed on created or mutable accounts of a program type that are not closed",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if acc.optional
+                    && (acc.create.is_some()
+                        || acc.init_if_needed
+                        || acc.ata.is_some()
+                        || acc.close.is_some())
+                {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) optional accounts cant be create, init_if_needed, an ata or closed",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if let Some(name) = acc
+                    .references()
+                    .find(|name| ix.accounts.get(*name).is_some_and(|other| other.optional))
+                {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) cant reference optional account {}",
+                        ix_name,
+                        acc_name,
+                        name
+                    ))?;
+                } else if acc.seed.is_some() && (acc.r#type.is_none() || acc.token_kind().is_some())
+                {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) seed is only allowed on accounts with a non token type",
+                        ix_name,
+                        acc_name
+                    ))?;
+                } else if let Some(kind) = acc.token_kind() {
+                    if acc.create.is_some() || acc.owner.is_some() {
+                        Err(anyhow::anyhow!(
+                            "id({}) account({}) token accounts cant be create or have an owner",
+                            ix_name,
+                            acc_name
+                        ))?;
+                    }
+                    if kind == TokenKind::Mint && acc.mint.is_some() {
+                        Err(anyhow::anyhow!(
+                            "id({}) account({}) a mint cant have a mint",
+                            ix_name,
+                            acc_name
+                        ))?;
+                    }
+                    if let Some(ata) = &acc.ata {
+                        ix.validate_ata(ix_name, acc_name, acc, ata)?;
+                    }
+                    for name in acc.references() {
+                        if !ix.accounts.contains_key(name) {
+                            Err(anyhow::anyhow!(
+                                "id({}) account({}) references undefined account {}",
+                                ix_name,
+                                acc_name,
+                                name
+                            ))?;
+                        }
+                    }
+                } else if acc.mint.is_some() || acc.authority.is_some() {
+                    Err(anyhow::anyhow!(
+                        "id({}) account({}) mint and authority are only allowed on token accounts",
+                        ix_name,
+                        acc_name
+                    ))?;
                 } else if let Some(ty) = &acc.r#type {
-                    let _account_def = self.accounts.get(ty).ok_or(anyhow::anyhow!(
+                    let account_def = self.accounts.get(ty).ok_or(anyhow::anyhow!(
                         "id({}) account({}) type {} not defined",
                         ix_name,
                         acc_name,
                         ty
                     ))?;
-
-                    // TODO check seed params from accounts and account seed
+                    if acc.init_if_needed && account_def.seed.is_none() {
+                        Err(anyhow::anyhow!(
+                            "id({}) account({}) init_if_needed accounts must have a type with a seed",
+                            ix_name,
+                            acc_name
+                        ))?;
+                    }
+                    if acc.create == Some(CreateKind::Keypair)
+                        && (account_def.seed.is_some() || acc.seed.is_some())
+                    {
+                        Err(anyhow::anyhow!(
+                            "id({}) account({}) keypair accounts cant have a seed or a type with a seed",
+                            ix_name,
+                            acc_name
+                        ))?;
+                    }
+                    if let Some(seed) = &acc.seed {
+                        ix.validate_seed(ix_name, acc_name, acc, ty, account_def, seed)?;
+                    }
                 }
+                self.validate_constraints(ix_name, ix, acc_name, acc)?;
                 idxs[acc.id] = 1;
             }
         }
 
         Ok(())
     }
+
+    /// Checks the constraints of an instruction account against the accounts,
+    /// fields and errors they reference.
+    fn validate_constraints(
+        &self,
+        ix_name: &str,
+        ix: &Ix,
+        acc_name: &str,
+        acc: &IxAccount,
+    ) -> Result<(), SolGenError> {
+        let constrained =
+            !acc.has_one.is_empty() || acc.address.is_some() || acc.constraint.is_some();
+        if constrained && (acc.create.is_some() || acc.program.is_some() || acc.sysvar.is_some()) {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) create, program and sysvar accounts cant have constraints",
+                ix_name,
+                acc_name
+            ))?;
+        } else if let Some(error) = acc
+            .constraint
+            .as_ref()
+            .and_then(|constraint| constraint.error.as_ref())
+            .filter(|error| !self.errors.contains_key(*error))
+        {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) constraint error {} not defined",
+                ix_name,
+                acc_name,
+                error
+            ))?;
+        }
+
+        if acc.has_one.is_empty() {
+            return Ok(());
+        }
+        let Some(Account {
+            payload: Message::Struct(fields),
+            ..
+        }) = acc.r#type.as_ref().and_then(|ty| self.accounts.get(ty))
+        else {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) has_one is only allowed on accounts with a non token type",
+                ix_name,
+                acc_name
+            ))?
+        };
+        for name in acc.has_one.iter() {
+            if name == acc_name || !ix.accounts.contains_key(name) {
+                Err(anyhow::anyhow!(
+                    "id({}) account({}) has_one {} must be another account",
+                    ix_name,
+                    acc_name,
+                    name
+                ))?;
+            } else if fields.get(name) != Some(&Type::PublicKey) {
+                Err(anyhow::anyhow!(
+                    "id({}) account({}) has_one {} is not a public_key field of {}",
+                    ix_name,
+                    acc_name,
+                    name,
+                    acc.r#type.as_deref().unwrap_or_default()
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the signer of a voucher is an account key or a public_key
+    /// field of a loaded account, and that the instruction passes the
+    /// instructions sysvar to look up the ed25519 instruction with.
+    fn validate_voucher(
+        &self,
+        ix_name: &str,
+        ix: &Ix,
+        voucher: &Voucher,
+    ) -> Result<(), SolGenError> {
+        let (acc_name, field) = voucher.signer();
+        let Some(acc) = ix.accounts.get(acc_name).filter(|acc| !acc.optional) else {
+            Err(anyhow::anyhow!(
+                "id({}) voucher signer {} must be a non optional account",
+                ix_name,
+                voucher.signer
+            ))?
+        };
+        if ix
+            .accounts
+            .values()
+            .filter(|acc| acc.sysvar == Some(SysvarKind::Instructions))
+            .count()
+            != 1
+        {
+            Err(anyhow::anyhow!(
+                "id({}) voucher needs one sysvar(instructions) account",
+                ix_name
+            ))?;
+        }
+        let Some(field) = field else {
+            return Ok(());
+        };
+        let fields = match acc.r#type.as_ref().and_then(|ty| self.accounts.get(ty)) {
+            Some(Account {
+                payload: Message::Struct(fields),
+                ..
+            }) if acc.create.is_none() && !acc.init_if_needed && acc.token_kind().is_none() => fields,
+            _ => Err(anyhow::anyhow!(
+                "id({}) voucher signer {} must be a field of a loaded account with a non token type",
+                ix_name,
+                voucher.signer
+            ))?,
+        };
+        if fields.get(field) != Some(&Type::PublicKey) {
+            Err(anyhow::anyhow!(
+                "id({}) voucher signer {} is not a public_key field of {}",
+                ix_name,
+                voucher.signer,
+                acc.r#type.as_deref().unwrap_or_default()
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// A program error, returned as a `ProgramError::Custom` code when a
+/// constraint referencing it fails.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Error {
+    pub code: u32,
+    #[serde(default)]
+    pub msg: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub struct Program {
     pub name: String,
     pub version: (u8, u8, u8),
+    #[serde(default)]
+    pub id: Option<Address>,
+    /// The pinocchio entrypoint `sol_ez::program!` expands to.
+    #[serde(default)]
+    pub entrypoint: Entrypoint,
+    /// Generates `#[must_use]` checked add and sub setters for the integer
+    /// fields of the accounts.
+    #[serde(default)]
+    pub checked_setters: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Entrypoint {
+    /// Parses every account before dispatching, with the default allocator.
+    #[default]
+    Standard,
+    /// Hands the unparsed input to the dispatcher.
+    Lazy,
+    /// The standard entrypoint without a heap allocator.
+    NoAllocator,
+}
+
+impl fmt::Display for Entrypoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entrypoint::Standard => write!(f, "standard"),
+            Entrypoint::Lazy => write!(f, "lazy"),
+            Entrypoint::NoAllocator => write!(f, "no_allocator"),
+        }
+    }
+}
+
+impl FromStr for Entrypoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Entrypoint::Standard),
+            "lazy" => Ok(Entrypoint::Lazy),
+            "no_allocator" => Ok(Entrypoint::NoAllocator),
+            s => Err(format!("Invalid entrypoint: {}", s)),
+        }
+    }
+}
+
+/// A base58 encoded public key.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Address(pub [u8; 32]);
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D>(deserializer: D) -> Result<Address, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Address::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0; 32];
+        match bs58::decode(s).onto(&mut bytes) {
+            Ok(32) => Ok(Address(bytes)),
+            _ => Err(format!("Invalid address: {}", s)),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub struct Ix {
     pub id: usize,
     #[serde(default)]
-    pub args: BTreeMap<String, Type>,
+    pub args: Vec<IxArg>,
     pub accounts: BTreeMap<String, IxAccount>,
+    /// The accounts passed after `accounts`, any number of them.
+    #[serde(default)]
+    pub remaining: Option<Remaining>,
+    #[serde(default)]
+    pub voucher: Option<Voucher>,
+    /// The type the instruction handler returns, set as the return data of
+    /// the transaction.
+    #[serde(default)]
+    pub returns: Option<Type>,
+}
+
+/// An off-chain signature required by an instruction, verified by an ed25519
+/// precompile instruction of the same transaction.
+///
+/// The voucher signs the raw args of the instruction only, without its
+/// discriminator, and nothing keeps it from being redeemed again. The args
+/// must bind it to the instruction and to a single use themselves, e.g. with
+/// a nonce the handler checks against an account and bumps.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Voucher {
+    /// The account whose key signs the voucher, or `account.field` for a
+    /// public_key field of a typed account.
+    pub signer: String,
+}
+
+impl Voucher {
+    /// Splits the signer into its account and field.
+    pub fn signer(&self) -> (&str, Option<&str>) {
+        match self.signer.split_once('.') {
+            Some((account, field)) => (account, Some(field)),
+            None => (&self.signer, None),
+        }
+    }
+}
+
+/// The type of the remaining accounts of an instruction, which are loaded into
+/// a `remaining` field.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Remaining {
+    pub r#type: String,
+    #[serde(default)]
+    pub mutable: bool,
+}
+
+impl Ix {
+    /// Checks that arg names are unique and that every explicit `offset` matches
+    /// the position the arg is serialized at.
+    fn validate_args(&self, ix_name: &str) -> Result<(), SolGenError> {
+        let mut offset = Some(0);
+        for (idx, arg) in self.args.iter().enumerate() {
+            if self.args[..idx].iter().any(|a| a.name == arg.name) {
+                Err(anyhow::anyhow!(
+                    "duplicate arg in {} args({})",
+                    ix_name,
+                    arg.name
+                ))?;
+            }
+            match (arg.offset, offset) {
+                (Some(expected), Some(actual)) if expected != actual => Err(anyhow::anyhow!(
+                    "id({}) arg({}) declared at offset {} but is laid out at offset {}",
+                    ix_name,
+                    arg.name,
+                    expected,
+                    actual
+                ))?,
+                (Some(_), None) => Err(anyhow::anyhow!(
+                    "id({}) arg({}) has an offset but follows a variable sized arg",
+                    ix_name,
+                    arg.name
+                ))?,
+                _ => {}
+            }
+            let size = my_idl::Type::from(arg.r#type.clone()).size();
+            offset = offset.zip(size).map(|(offset, size)| offset + size);
+        }
+        Ok(())
+    }
+
+    /// Checks an associated token account, and that its instruction passes
+    /// the programs needed to create it.
+    fn validate_ata(
+        &self,
+        ix_name: &str,
+        acc_name: &str,
+        acc: &IxAccount,
+        ata: &Ata,
+    ) -> Result<(), SolGenError> {
+        let has_program = |kinds: &[ProgramKind]| {
+            self.accounts
+                .values()
+                .filter(|acc| acc.program.is_some_and(|p| kinds.contains(&p)))
+                .count()
+        };
+        if acc.signed || acc.r#type.is_some() || acc.mint.is_some() || acc.authority.is_some() {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) ata accounts cant be signed or have a type, mint or authority",
+                ix_name,
+                acc_name
+            ))?;
+        } else if ata.init_if_needed && !acc.mutable {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) init_if_needed ata accounts must be mutable",
+                ix_name,
+                acc_name
+            ))?;
+        } else if ata.init_if_needed != ata.payer.is_some() {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) ata accounts need a payer exactly when init_if_needed",
+                ix_name,
+                acc_name
+            ))?;
+        } else if ata.init_if_needed
+            && (has_program(&[ProgramKind::System]) != 1
+                || has_program(&[ProgramKind::Token, ProgramKind::Token2022]) != 1
+                || has_program(&[ProgramKind::AssociatedToken]) != 1)
+        {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) init_if_needed ata accounts need one system, token and associated_token program account",
+                ix_name,
+                acc_name
+            ))?;
+        }
+        if let Some(payer) = ata
+            .payer
+            .as_ref()
+            .and_then(|payer| self.accounts.get(payer))
+        {
+            if !payer.mutable || !payer.signed {
+                Err(anyhow::anyhow!(
+                    "id({}) account({}) ata payer must be mutable and signed",
+                    ix_name,
+                    acc_name
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the `seed` of an account lists an instruction account for every
+    /// input of the seed of its type, and that its address can be checked.
+    fn validate_seed(
+        &self,
+        ix_name: &str,
+        acc_name: &str,
+        acc: &IxAccount,
+        ty: &str,
+        account_def: &Account,
+        seed: &[String],
+    ) -> Result<(), SolGenError> {
+        let Some(account_seed) = &account_def.seed else {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) seed is only allowed on accounts with a type with a seed",
+                ix_name,
+                acc_name
+            ))?
+        };
+        if seed.len() != account_seed.func.inputs.len() {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) seed must list an account for each of the {} inputs of the seed of {}",
+                ix_name,
+                acc_name,
+                account_seed.func.inputs.len(),
+                ty
+            ))?;
+        }
+        if let Some(name) = seed.iter().find(|name| !self.accounts.contains_key(*name)) {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) seed references undefined account {}",
+                ix_name,
+                acc_name,
+                name
+            ))?;
+        }
+        // created accounts are checked against the seeds they are created
+        // with, loaded accounts against the bump they store
+        if acc.create.is_none() && !acc.init_if_needed && !account_seed.bump {
+            Err(anyhow::anyhow!(
+                "id({}) account({}) seed is only allowed on loaded accounts with a type with a bump",
+                ix_name,
+                acc_name
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// An instruction argument.
+///
+/// Args are serialized in the order they are declared. `offset` is optional and
+/// pins the byte offset of the arg within the instruction data (after the
+/// discriminator), so reordering args is caught by [`Config::validate`].
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct IxArg {
+    pub name: String,
+    pub r#type: Type,
+    #[serde(default)]
+    pub offset: Option<usize>,
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -80,16 +688,264 @@ pub enum DiscriminatorType {
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub struct IxAccount {
     pub id: usize,
+    /// Creates the account, at a program derived address with `create = true`
+    /// or `create = { kind = "pda" }`, or at the key of a keypair signing the
+    /// transaction with `create = { kind = "keypair" }`.
+    #[serde(default, deserialize_with = "create_kind")]
+    pub create: Option<CreateKind>,
+    /// Creates the account if it does not exist yet, and loads it otherwise.
     #[serde(default)]
-    pub create: bool,
+    pub init_if_needed: bool,
     #[serde(default)]
     pub mutable: bool,
     #[serde(default)]
     pub signed: bool,
+    /// The account may be left out by passing the program id in its place.
+    #[serde(default)]
+    pub optional: bool,
+    /// The read only account may also be passed as a mutable account of the
+    /// instruction.
+    #[serde(default)]
+    pub allow_duplicate: bool,
+    /// Changes to the account are only written by `apply` in the handler,
+    /// instead of by the dispatcher once the handler succeeded.
+    #[serde(default)]
+    pub manual_apply: bool,
+    /// Whether the account must still be rent exempt once the instruction
+    /// handler returns, checked by default for the program accounts the
+    /// instruction writes.
+    #[serde(default)]
+    pub rent_exempt: Option<bool>,
     #[serde(default)]
     pub r#type: Option<String>,
     #[serde(default)]
     pub seed: Option<Vec<String>>,
+    #[serde(default)]
+    pub owner: Option<AccountOwner>,
+    #[serde(default)]
+    pub program: Option<ProgramKind>,
+    #[serde(default)]
+    pub sysvar: Option<SysvarKind>,
+    /// The account of the mint a token account must hold.
+    #[serde(default)]
+    pub mint: Option<String>,
+    /// The account that must own a token account or be the authority of a mint.
+    #[serde(default)]
+    pub authority: Option<String>,
+    #[serde(default)]
+    pub ata: Option<Ata>,
+    /// The account receiving the lamports of this account, closed once the
+    /// instruction succeeds.
+    #[serde(default)]
+    pub close: Option<String>,
+    /// Fields of the account that must hold the key of the sibling account of
+    /// the same name.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub has_one: Vec<String>,
+    /// The key the account must have.
+    #[serde(default)]
+    pub address: Option<Address>,
+    #[serde(default)]
+    pub constraint: Option<Constraint>,
+}
+
+/// A rust expression over the instruction accounts that must hold once they
+/// are loaded, e.g. `claim.amount_acquired >= claim_config.min_amount_to_claim`.
+///
+/// Written either as the expression alone, or as a table naming the error
+/// returned when it does not hold.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(from = "ConstraintDef")]
+pub struct Constraint {
+    pub expr: String,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConstraintDef {
+    Expr(String),
+    Table {
+        expr: String,
+        #[serde(default)]
+        error: Option<String>,
+    },
+}
+
+impl From<ConstraintDef> for Constraint {
+    fn from(value: ConstraintDef) -> Self {
+        match value {
+            ConstraintDef::Expr(expr) => Constraint { expr, error: None },
+            ConstraintDef::Table { expr, error } => Constraint { expr, error },
+        }
+    }
+}
+
+/// Deserializes either a single string or a list of strings.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
+
+/// How a `create` account is created.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreateKind {
+    /// At a program derived address of the program.
+    Pda,
+    /// At the key of a new keypair, which must sign the transaction.
+    Keypair,
+}
+
+/// Deserializes either a flag creating a program derived account, or a table
+/// naming the kind of account created.
+fn create_kind<'de, D>(deserializer: D) -> Result<Option<CreateKind>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CreateDef {
+        Flag(bool),
+        Table { kind: CreateKind },
+    }
+
+    Ok(match CreateDef::deserialize(deserializer)? {
+        CreateDef::Flag(create) => create.then_some(CreateKind::Pda),
+        CreateDef::Table { kind } => Some(kind),
+    })
+}
+
+/// An associated token account, at the address derived from its owner and
+/// mint.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Ata {
+    pub mint: String,
+    pub owner: String,
+    /// Creates the account through the associated token program if it does
+    /// not exist yet.
+    #[serde(default)]
+    pub init_if_needed: bool,
+    /// The account paying for the creation of the account.
+    #[serde(default)]
+    pub payer: Option<String>,
+}
+
+impl IxAccount {
+    /// The other instruction accounts this account is checked against or
+    /// loaded with.
+    fn references(&self) -> impl Iterator<Item = &String> {
+        let ata_accounts = self
+            .ata
+            .iter()
+            .flat_map(|ata| [&ata.mint, &ata.owner].into_iter().chain(&ata.payer));
+        self.mint
+            .iter()
+            .chain(&self.authority)
+            .chain(ata_accounts)
+            .chain(&self.close)
+            .chain(&self.has_one)
+            .chain(self.seed.iter().flatten())
+    }
+
+    /// Whether the account is a program account the instruction creates or
+    /// writes, and does not close.
+    pub fn is_written_program_account(&self) -> bool {
+        let written = self.create.is_some()
+            || self.init_if_needed
+            || (self.mutable && self.r#type.is_some() && self.owner.is_none());
+        written && self.token_kind().is_none() && self.close.is_none()
+    }
+
+    /// The kind of token program account, for the `spl_token::account` and
+    /// `spl_token::mint` types and associated token accounts.
+    pub fn token_kind(&self) -> Option<TokenKind> {
+        if self.ata.is_some() {
+            return Some(TokenKind::Account);
+        }
+        match self.r#type.as_deref() {
+            Some("spl_token::account") => Some(TokenKind::Account),
+            Some("spl_token::mint") => Some(TokenKind::Mint),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TokenKind {
+    Account,
+    Mint,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Account => write!(f, "spl_token::account"),
+            TokenKind::Mint => write!(f, "spl_token::mint"),
+        }
+    }
+}
+
+/// A program with a well known address.
+#[derive(Debug, PartialEq, Eq, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramKind {
+    System,
+    Token,
+    Token2022,
+    AssociatedToken,
+}
+
+/// A sysvar with a well known address.
+#[derive(Debug, PartialEq, Eq, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SysvarKind {
+    Clock,
+    Rent,
+    EpochSchedule,
+    Instructions,
+}
+
+/// The expected owner of an instruction account.
+///
+/// Accounts with a `type` payload default to being owned by the program.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AccountOwner {
+    System,
+    Address(Address),
+}
+
+impl<'de> Deserialize<'de> for AccountOwner {
+    fn deserialize<D>(deserializer: D) -> Result<AccountOwner, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        AccountOwner::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for AccountOwner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "system" => Ok(AccountOwner::System),
+            s => Address::from_str(s).map(AccountOwner::Address),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -203,3 +1059,177 @@ pub enum Type {
     DynamicArray(Box<Type>),
     Defined(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ix(args: Vec<(&str, Type, Option<usize>)>) -> Ix {
+        Ix {
+            id: 0,
+            args: args
+                .into_iter()
+                .map(|(name, ty, offset)| IxArg {
+                    name: name.to_string(),
+                    r#type: ty,
+                    offset,
+                })
+                .collect(),
+            accounts: BTreeMap::new(),
+            remaining: None,
+            voucher: None,
+            returns: None,
+        }
+    }
+
+    #[test]
+    fn test_arg_offsets() {
+        let valid = ix(vec![
+            ("amount", Type::U64, Some(0)),
+            ("authority", Type::PublicKey, Some(8)),
+            ("bump", Type::U8, None),
+        ]);
+        assert!(valid.validate_args("ix").is_ok());
+
+        let reordered = ix(vec![
+            ("authority", Type::PublicKey, None),
+            ("amount", Type::U64, Some(0)),
+        ]);
+        assert!(reordered.validate_args("ix").is_err());
+
+        let after_variable = ix(vec![
+            ("name", Type::String, None),
+            ("amount", Type::U64, Some(4)),
+        ]);
+        assert!(after_variable.validate_args("ix").is_err());
+
+        let duplicate = ix(vec![
+            ("amount", Type::U64, None),
+            ("amount", Type::U8, None),
+        ]);
+        assert!(duplicate.validate_args("ix").is_err());
+    }
+
+    #[test]
+    fn test_account_owner() {
+        assert_eq!(AccountOwner::from_str("system"), Ok(AccountOwner::System));
+        assert_eq!(
+            AccountOwner::from_str("11111111111111111111111111111111"),
+            Ok(AccountOwner::Address(Address([0; 32])))
+        );
+        assert!(AccountOwner::from_str("not a key").is_err());
+        assert!(Address::from_str("1111").is_err());
+    }
+
+    #[test]
+    fn test_voucher_signer() {
+        let voucher = |signer: &str| Voucher {
+            signer: signer.to_string(),
+        };
+        assert_eq!(voucher("manager").signer(), ("manager", None));
+        assert_eq!(
+            voucher("config.voucher_authority").signer(),
+            ("config", Some("voucher_authority"))
+        );
+    }
+
+    #[test]
+    fn test_create_kind() {
+        let config = |create: &str, ty: &str| {
+            let src = format!(
+                r#"
+                [program]
+                name = "counter"
+                version = [0, 1, 0]
+
+                [accounts.count]
+                id = 0
+                seed = {{ func = "[user] => 'count' + user", bump = true }}
+                payload = {{ type = "struct", data = {{ value = "u8" }} }}
+
+                [accounts.keypair_count]
+                id = 1
+                payload = {{ type = "struct", data = {{ value = "u8" }} }}
+
+                [ix_config]
+                discriminator_size = 4
+                discriminator_type = "hash"
+
+                [ix.initalize]
+                id = 0
+
+                [ix.initalize.accounts]
+                user = {{ id = 0, mutable = true, signed = true }}
+                count = {{ id = 1, type = "{}", create = {} }}
+                "#,
+                ty, create
+            );
+            Config::deserialize(toml::Deserializer::new(&src)).unwrap()
+        };
+        let create = |config: &Config| config.ix["initalize"].accounts["count"].create;
+
+        let pda = config("true", "count");
+        assert_eq!(create(&pda), Some(CreateKind::Pda));
+        assert!(pda.validate().is_ok());
+
+        let not_created = config("false", "count");
+        assert_eq!(create(&not_created), None);
+
+        let keypair = config(r#"{ kind = "keypair" }"#, "keypair_count");
+        assert_eq!(create(&keypair), Some(CreateKind::Keypair));
+        assert!(keypair.validate().is_ok());
+
+        let seeded_keypair = config(r#"{ kind = "keypair" }"#, "count");
+        assert!(seeded_keypair.validate().is_err());
+    }
+
+    #[test]
+    fn test_account_seed() {
+        let config = |count: &str| {
+            let src = format!(
+                r#"
+                [program]
+                name = "counter"
+                version = [0, 1, 0]
+
+                [accounts.count]
+                id = 0
+                seed = {{ func = "[user] => 'count' + user", bump = true }}
+                payload = {{ type = "struct", data = {{ value = "u8" }} }}
+
+                [accounts.unbumped_count]
+                id = 1
+                seed = {{ func = "[user] => 'count' + user", bump = false }}
+                payload = {{ type = "struct", data = {{ value = "u8" }} }}
+
+                [ix_config]
+                discriminator_size = 4
+                discriminator_type = "hash"
+
+                [ix.increment]
+                id = 0
+
+                [ix.increment.accounts]
+                user = {{ id = 0, signed = true }}
+                count = {{ id = 1, {} }}
+                "#,
+                count
+            );
+            Config::deserialize(toml::Deserializer::new(&src)).unwrap()
+        };
+
+        let loaded = config(r#"type = "count", mutable = true, seed = ["user"]"#);
+        assert!(loaded.validate().is_ok());
+        let created = config(r#"type = "unbumped_count", create = true, seed = ["user"]"#);
+        assert!(created.validate().is_ok());
+
+        let unbumped = config(r#"type = "unbumped_count", mutable = true, seed = ["user"]"#);
+        assert!(unbumped.validate().is_err());
+        let too_many = config(r#"type = "count", mutable = true, seed = ["user", "user"]"#);
+        assert!(too_many.validate().is_err());
+        let undefined = config(r#"type = "count", mutable = true, seed = ["owner"]"#);
+        assert!(undefined.validate().is_err());
+        let untyped = config(r#"mutable = true, seed = ["user"]"#);
+        assert!(untyped.validate().is_err());
+    }
+}
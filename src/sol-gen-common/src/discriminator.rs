@@ -4,6 +4,15 @@ use crate::my_idl::{Account, Instruction};
 
 pub type Discriminator = Vec<u8>;
 
+/// The discriminator size of accounts that don't configure one.
+pub const DEFAULT_ACCOUNT_DISCRIMINATOR_SIZE: u8 = 4;
+
+/// Whether `discriminator` is the one written to closed accounts, which no
+/// account type may use.
+pub fn is_closed_discriminator(discriminator: &[u8]) -> bool {
+    discriminator.iter().all(|byte| *byte == u8::MAX)
+}
+
 pub trait DiscriminatorGen {
     type Seed;
 
@@ -1,4 +1,5 @@
 pub mod config;
 pub mod discriminator;
 pub mod error;
+pub mod lock;
 pub mod my_idl;
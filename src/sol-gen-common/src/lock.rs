@@ -0,0 +1,395 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    discriminator::{DiscriminatorGen, HashDiscriminatorGen},
+    my_idl::{Account, Field, InstructionAccount, IxAccountState, MyIdl, Type},
+};
+
+/// The recorded wire layout of a program.
+///
+/// A `Lock` is written next to the contract config on every generation and is
+/// compared against the layout of the next generation, so changes to the
+/// discriminators, instruction data or account data of a deployed program are
+/// surfaced before they ship.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lock {
+    #[serde(default)]
+    pub ix: BTreeMap<String, IxLock>,
+    #[serde(default)]
+    pub accounts: BTreeMap<String, AccountLock>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IxLock {
+    pub discriminator: Vec<u8>,
+    #[serde(default)]
+    pub args: Vec<ArgLock>,
+    #[serde(default)]
+    pub accounts: Vec<IxAccountLock>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArgLock {
+    pub name: String,
+    pub r#type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IxAccountLock {
+    pub name: String,
+    pub index: u8,
+    pub writable: bool,
+    pub signer: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountLock {
+    pub discriminator: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_size: Option<usize>,
+    #[serde(default)]
+    pub fields: Vec<ArgLock>,
+}
+
+/// A single difference between two [`Lock`]s.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LockChange {
+    /// Whether the change breaks clients or accounts of the recorded layout.
+    pub breaking: bool,
+    pub path: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl Lock {
+    pub fn from_idl(idl: &MyIdl) -> Self {
+        Lock {
+            ix: idl
+                .instructions
+                .iter()
+                .map(|ix| {
+                    let lock = IxLock {
+                        discriminator: HashDiscriminatorGen::from_instruction(
+                            &idl.name,
+                            ix,
+                            idl.instruction_discriminator_size,
+                        ),
+                        args: field_layout(&ix.args),
+                        accounts: ix.accounts.iter().map(IxAccountLock::from).collect(),
+                    };
+                    (ix.name.clone(), lock)
+                })
+                .collect(),
+            accounts: idl
+                .accounts
+                .iter()
+                .map(|acc| (acc.name.clone(), AccountLock::new(&idl.name, acc)))
+                .collect(),
+        }
+    }
+
+    /// Returns every difference between the recorded layout and `new`.
+    ///
+    /// Removing or changing a recorded instruction or account is breaking,
+    /// adding a new one is not.
+    pub fn diff(&self, new: &Lock) -> Vec<LockChange> {
+        let mut changes = Vec::new();
+
+        for (name, old_ix, new_ix) in join(&self.ix, &new.ix) {
+            let path = format!("ix({})", name);
+            let (old_ix, new_ix) = match (old_ix, new_ix) {
+                (Some(old_ix), Some(new_ix)) => (old_ix, new_ix),
+                (old_ix, new_ix) => {
+                    changes.push(LockChange::new(
+                        old_ix.is_some(),
+                        path,
+                        old_ix.map(|ix| fmt_fields(&ix.args)),
+                        new_ix.map(|ix| fmt_fields(&ix.args)),
+                    ));
+                    continue;
+                }
+            };
+            if old_ix.discriminator != new_ix.discriminator {
+                changes.push(LockChange::changed(
+                    format!("{} discriminator", path),
+                    format!("{:?}", old_ix.discriminator),
+                    format!("{:?}", new_ix.discriminator),
+                ));
+            }
+            if old_ix.args != new_ix.args {
+                changes.push(LockChange::changed(
+                    format!("{} args", path),
+                    fmt_fields(&old_ix.args),
+                    fmt_fields(&new_ix.args),
+                ));
+            }
+            let len = old_ix.accounts.len().max(new_ix.accounts.len());
+            for idx in 0..len {
+                let old_acc = old_ix.accounts.get(idx);
+                let new_acc = new_ix.accounts.get(idx);
+                if old_acc != new_acc {
+                    changes.push(LockChange::new(
+                        true,
+                        format!("{} account {}", path, idx),
+                        old_acc.map(ToString::to_string),
+                        new_acc.map(ToString::to_string),
+                    ));
+                }
+            }
+        }
+
+        for (name, old_acc, new_acc) in join(&self.accounts, &new.accounts) {
+            let path = format!("account({})", name);
+            let (old_acc, new_acc) = match (old_acc, new_acc) {
+                (Some(old_acc), Some(new_acc)) => (old_acc, new_acc),
+                (old_acc, new_acc) => {
+                    changes.push(LockChange::new(
+                        old_acc.is_some(),
+                        path,
+                        old_acc.map(|acc| fmt_fields(&acc.fields)),
+                        new_acc.map(|acc| fmt_fields(&acc.fields)),
+                    ));
+                    continue;
+                }
+            };
+            if old_acc.discriminator != new_acc.discriminator {
+                changes.push(LockChange::changed(
+                    format!("{} discriminator", path),
+                    format!("{:?}", old_acc.discriminator),
+                    format!("{:?}", new_acc.discriminator),
+                ));
+            }
+            if old_acc.data_size != new_acc.data_size {
+                changes.push(LockChange::changed(
+                    format!("{} data_size", path),
+                    fmt_size(old_acc.data_size),
+                    fmt_size(new_acc.data_size),
+                ));
+            }
+            if old_acc.fields != new_acc.fields {
+                changes.push(LockChange::changed(
+                    format!("{} fields", path),
+                    fmt_fields(&old_acc.fields),
+                    fmt_fields(&new_acc.fields),
+                ));
+            }
+        }
+
+        changes
+    }
+}
+
+impl AccountLock {
+    fn new(program_name: &str, account: &Account) -> Self {
+        let discriminator_size = account.discriminator_size();
+        let bump = Field {
+            name: String::from("bump"),
+            ty: Type::U8,
+        };
+        let fields = || {
+            account.fields.iter().chain(
+                account
+                    .seed
+                    .as_ref()
+                    .filter(|seed| seed.bump)
+                    .map(|_| &bump),
+            )
+        };
+
+        AccountLock {
+            discriminator: HashDiscriminatorGen::from_account(
+                program_name,
+                account,
+                discriminator_size as usize,
+            ),
+            data_size: fields().map(|field| field.ty.size()).sum(),
+            fields: field_layout(fields()),
+        }
+    }
+}
+
+impl From<&InstructionAccount> for IxAccountLock {
+    fn from(acc: &InstructionAccount) -> Self {
+        IxAccountLock {
+            name: acc.name.clone(),
+            index: acc.id,
+            writable: acc.state != IxAccountState::Immutable,
+            signer: acc.is_signed,
+            r#type: acc
+                .payload
+                .as_ref()
+                .map(|payload| payload.name.clone())
+                .or_else(|| acc.token.as_ref().map(|token| token.kind.to_string()))
+                .or_else(|| acc.known.map(|known| known.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for IxAccountLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(ty) = &self.r#type {
+            write!(f, ": {}", ty)?;
+        }
+        match (self.writable, self.signer) {
+            (true, true) => write!(f, " (writable, signer)"),
+            (true, false) => write!(f, " (writable)"),
+            (false, true) => write!(f, " (signer)"),
+            (false, false) => Ok(()),
+        }
+    }
+}
+
+impl LockChange {
+    fn new(breaking: bool, path: String, old: Option<String>, new: Option<String>) -> Self {
+        LockChange {
+            breaking,
+            path,
+            old,
+            new,
+        }
+    }
+
+    fn changed(path: String, old: String, new: String) -> Self {
+        LockChange::new(true, path, Some(old), Some(new))
+    }
+}
+
+impl fmt::Display for LockChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "~ {}: {} -> {}", self.path, old, new),
+            (Some(old), None) => write!(f, "- {}: {}", self.path, old),
+            (None, Some(new)) => write!(f, "+ {}: {}", self.path, new),
+            (None, None) => write!(f, "  {}", self.path),
+        }
+    }
+}
+
+/// Pairs up the entries of two maps by key.
+fn join<'a, T>(
+    old: &'a BTreeMap<String, T>,
+    new: &'a BTreeMap<String, T>,
+) -> impl Iterator<Item = (&'a String, Option<&'a T>, Option<&'a T>)> {
+    old.keys()
+        .chain(new.keys().filter(|key| !old.contains_key(*key)))
+        .map(|key| (key, old.get(key), new.get(key)))
+}
+
+fn field_layout<'a>(fields: impl IntoIterator<Item = &'a Field>) -> Vec<ArgLock> {
+    let mut offset = Some(0);
+    fields
+        .into_iter()
+        .map(|field| {
+            let lock = ArgLock {
+                name: field.name.clone(),
+                r#type: field.ty.to_string(),
+                offset,
+            };
+            offset = offset
+                .zip(field.ty.size())
+                .map(|(offset, size)| offset + size);
+            lock
+        })
+        .collect()
+}
+
+fn fmt_fields(fields: &[ArgLock]) -> String {
+    let fields = fields
+        .iter()
+        .map(|field| match field.offset {
+            Some(offset) => format!("{}: {} @{}", field.name, field.r#type, offset),
+            None => format!("{}: {}", field.name, field.r#type),
+        })
+        .collect::<Vec<_>>();
+    format!("({})", fields.join(", "))
+}
+
+fn fmt_size(size: Option<usize>) -> String {
+    size.map(|size| size.to_string())
+        .unwrap_or_else(|| String::from("variable"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arg(name: &str, ty: &str, offset: usize) -> ArgLock {
+        ArgLock {
+            name: name.to_string(),
+            r#type: ty.to_string(),
+            offset: Some(offset),
+        }
+    }
+
+    fn lock() -> Lock {
+        Lock {
+            ix: BTreeMap::from([(
+                String::from("update"),
+                IxLock {
+                    discriminator: vec![1, 2, 3, 4],
+                    args: vec![arg("amount", "u64", 0), arg("bump", "u8", 8)],
+                    accounts: vec![IxAccountLock {
+                        name: String::from("config"),
+                        index: 0,
+                        writable: true,
+                        signer: false,
+                        r#type: Some(String::from("config")),
+                    }],
+                },
+            )]),
+            accounts: BTreeMap::from([(
+                String::from("config"),
+                AccountLock {
+                    discriminator: vec![5, 6, 7, 8],
+                    data_size: Some(8),
+                    fields: vec![arg("amount", "u64", 0)],
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        assert_eq!(lock().diff(&lock()), vec![]);
+    }
+
+    #[test]
+    fn test_diff_added() {
+        let mut new = lock();
+        new.ix.insert(String::from("create"), IxLock::default());
+        let changes = lock().diff(&new);
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].breaking);
+        assert_eq!(changes[0].to_string(), "+ ix(create): ()");
+    }
+
+    #[test]
+    fn test_diff_breaking() {
+        let mut new = lock();
+        let ix = new.ix.get_mut("update").unwrap();
+        ix.args.swap(0, 1);
+        ix.accounts[0].signer = true;
+        new.accounts.get_mut("config").unwrap().data_size = Some(9);
+
+        let changes = lock().diff(&new);
+        assert!(changes.iter().all(|change| change.breaking));
+        assert_eq!(
+            changes
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "~ ix(update) args: (amount: u64 @0, bump: u8 @8) -> (bump: u8 @8, amount: u64 @0)",
+                "~ ix(update) account 0: config: config (writable) -> config: config (writable, signer)",
+                "~ account(config) data_size: 8 -> 9",
+            ]
+        );
+    }
+}
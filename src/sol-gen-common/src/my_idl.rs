@@ -1,11 +1,24 @@
-use crate::config;
+use std::{collections::BTreeMap, fmt};
+
+use crate::{config, discriminator::DEFAULT_ACCOUNT_DISCRIMINATOR_SIZE};
 
 pub struct MyIdl {
     pub version: Version,
     pub name: String,
+    pub program_id: Option<[u8; 32]>,
     pub accounts: Vec<Account>,
     pub instructions: Vec<Instruction>,
     pub instruction_discriminator_size: usize,
+    pub errors: Vec<Error>,
+    /// Generates checked setters for the integer fields of the accounts.
+    pub checked_setters: bool,
+}
+
+/// A program error, returned by failing account constraints.
+pub struct Error {
+    pub name: String,
+    pub code: u32,
+    pub msg: Option<String>,
 }
 
 pub struct Version {
@@ -22,6 +35,16 @@ pub struct Account {
     pub discriminator: Option<AccountDiscriminator>,
 }
 
+impl Account {
+    /// The size of the discriminator prefixing the account data.
+    pub fn discriminator_size(&self) -> u8 {
+        self.discriminator
+            .as_ref()
+            .map(|discriminator| discriminator.size)
+            .unwrap_or(DEFAULT_ACCOUNT_DISCRIMINATOR_SIZE)
+    }
+}
+
 pub struct AccountDiscriminator {
     pub size: u8,
 }
@@ -31,6 +54,7 @@ pub struct AccountSeed {
     pub seeds: Vec<Seed>,
 }
 
+#[derive(Clone)]
 pub enum Seed {
     Defined(String),
     Input(String),
@@ -46,6 +70,28 @@ pub struct Instruction {
     pub name: String,
     pub accounts: Vec<InstructionAccount>,
     pub args: Vec<Field>,
+    pub remaining: Option<RemainingAccounts>,
+    pub voucher: Option<Voucher>,
+    /// Borsh serialized as the return data of the instruction.
+    pub returns: Option<Type>,
+}
+
+/// An ed25519 signature over the instruction data, checked before the
+/// instruction is handled.
+pub struct Voucher {
+    /// The account whose key, or public_key `field`, signs the voucher.
+    pub account: String,
+    pub field: Option<String>,
+    /// The instructions sysvar account the ed25519 instruction is looked up
+    /// with.
+    pub instructions: String,
+}
+
+/// The accounts passed after the accounts of an instruction, all of the same
+/// type.
+pub struct RemainingAccounts {
+    pub payload: InstructionAccountData,
+    pub is_mutable: bool,
 }
 
 pub struct InstructionAccount {
@@ -53,8 +99,94 @@ pub struct InstructionAccount {
     pub name: String,
     pub state: IxAccountState,
     pub is_signed: bool,
-    pub seed: Option<Vec<String>>,
+    /// Passed as the program id when absent.
+    pub optional: bool,
+    /// May alias a mutable account, so it is not checked to be unique.
+    pub allow_duplicate: bool,
+    /// Only written by `apply`, not once the handler returns.
+    pub manual_apply: bool,
+    /// Checked to still be rent exempt once the handler returns.
+    pub rent_exempt: bool,
+    /// The seeds of the account type, with its inputs resolved to the
+    /// instruction accounts whose keys they are.
+    pub seed: Option<AccountSeed>,
     pub payload: Option<InstructionAccountData>,
+    pub owner: Option<AccountOwner>,
+    pub known: Option<KnownAccount>,
+    pub token: Option<TokenAccount>,
+    /// The account receiving the lamports of this account when it is closed.
+    pub close: Option<String>,
+    pub constraints: Constraints,
+}
+
+/// The checks run on an instruction account once every account is loaded.
+pub struct Constraints {
+    pub has_one: Vec<String>,
+    pub address: Option<[u8; 32]>,
+    pub expr: Option<String>,
+    /// The error returned when `expr` does not hold, instead of the framework
+    /// error.
+    pub error: Option<String>,
+}
+
+impl Constraints {
+    pub fn is_empty(&self) -> bool {
+        self.has_one.is_empty() && self.address.is_none() && self.expr.is_none()
+    }
+}
+
+/// An account owned by a token program, with the accounts it is checked
+/// against when loaded.
+pub struct TokenAccount {
+    pub kind: config::TokenKind,
+    pub mint: Option<String>,
+    pub authority: Option<String>,
+    pub ata: Option<AssociatedTokenAccount>,
+}
+
+/// A token account at the associated token address of its authority and mint.
+pub struct AssociatedTokenAccount {
+    pub init_if_needed: Option<AtaInit>,
+}
+
+/// The accounts used to create an associated token account that does not
+/// exist yet.
+pub struct AtaInit {
+    pub payer: String,
+    pub system_program: String,
+    pub token_program: String,
+    pub associated_token_program: String,
+}
+
+/// An account with a well known address, checked by key when loaded.
+#[derive(Clone, Copy)]
+pub enum KnownAccount {
+    Program(KnownProgram),
+    Sysvar(KnownSysvar),
+}
+
+#[derive(Clone, Copy)]
+pub enum KnownProgram {
+    System,
+    Token,
+    Token2022,
+    AssociatedToken,
+}
+
+#[derive(Clone, Copy)]
+pub enum KnownSysvar {
+    Clock,
+    Rent,
+    EpochSchedule,
+    Instructions,
+}
+
+/// The owner an instruction account is checked against when loaded.
+pub enum AccountOwner {
+    /// The program being dispatched to.
+    Program,
+    System,
+    Key([u8; 32]),
 }
 
 pub struct InstructionAccountData {
@@ -65,6 +197,7 @@ pub struct InstructionAccountData {
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum IxAccountState {
     Create,
+    InitIfNeeded,
     Immutable,
     Mutable,
 }
@@ -100,8 +233,76 @@ pub enum Type {
     Defined(String),
 }
 
+impl Type {
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::U8
+                | Type::U16
+                | Type::U32
+                | Type::U64
+                | Type::U128
+                | Type::I8
+                | Type::I16
+                | Type::I32
+                | Type::I64
+                | Type::I128
+        )
+    }
+
+    /// The borsh serialized size of the type, `None` if the size is variable.
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            Type::Bool | Type::U8 | Type::I8 => Some(1),
+            Type::U16 | Type::I16 => Some(2),
+            Type::U32 | Type::I32 => Some(4),
+            Type::U64 | Type::I64 => Some(8),
+            Type::U128 | Type::I128 => Some(16),
+            Type::PublicKey => Some(32),
+            Type::FixedArray(ty, n) => ty.size().map(|size| size * n),
+            Type::Bytes
+            | Type::String
+            | Type::Option(_)
+            | Type::DynamicArray(_)
+            | Type::Defined(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Bool => write!(f, "bool"),
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::U128 => write!(f, "u128"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::I128 => write!(f, "i128"),
+            Type::Bytes => write!(f, "bytes"),
+            Type::String => write!(f, "string"),
+            Type::PublicKey => write!(f, "public_key"),
+            Type::Option(ty) => write!(f, "option<{}>", ty),
+            Type::FixedArray(ty, n) => write!(f, "[{}; {}]", ty, n),
+            Type::DynamicArray(ty) => write!(f, "vec<{}>", ty),
+            Type::Defined(ty) => write!(f, "{}", ty),
+        }
+    }
+}
+
 impl From<config::Config> for MyIdl {
     fn from(value: config::Config) -> Self {
+        // the inputs of the account seeds, which the seeds of instruction
+        // accounts are listed in the order of
+        let seed_inputs = value
+            .accounts
+            .iter()
+            .filter_map(|(name, acc)| Some((name.clone(), acc.seed.as_ref()?.func.inputs.clone())))
+            .collect::<BTreeMap<_, _>>();
         let accounts: Vec<_> = value
             .accounts
             .into_iter()
@@ -135,53 +336,172 @@ impl From<config::Config> for MyIdl {
             })
             .collect();
 
+        let account_data = |ty: String| InstructionAccountData {
+            discriminator_size: accounts
+                .iter()
+                .find(|acc| acc.name.as_str() == &ty)
+                .as_ref()
+                .expect("account type missing")
+                .discriminator_size(),
+            name: ty,
+        };
+
         let instructions = {
             let mut sorted = value.ix.into_iter().map(|x| x).collect::<Vec<_>>();
             sorted.sort_by(|a, b| a.1.id.cmp(&b.1.id));
             sorted
         }
         .into_iter()
-        .map(|(name, ix)| Instruction {
-            id: ix.id as u8,
-            name,
-            accounts: {
-                let mut sorted = ix.accounts.into_iter().map(|x| x).collect::<Vec<_>>();
-                sorted.sort_by(|a, b| a.1.id.cmp(&b.1.id));
-                sorted
-            }
-            .into_iter()
-            .map(|(name, acc)| InstructionAccount {
-                id: acc.id as u8,
-                name,
-                state: match (acc.create, acc.mutable) {
-                    (true, _) => IxAccountState::Create,
-                    (false, true) => IxAccountState::Mutable,
-                    (false, false) => IxAccountState::Immutable,
-                },
-                is_signed: acc.signed,
-                seed: acc.seed,
-                payload: acc.r#type.map(|ty| InstructionAccountData {
-                    discriminator_size: accounts
+        .map(|(name, ix)| {
+            // the programs an instruction passes to create its accounts
+            let program = |kinds: &[config::ProgramKind]| {
+                ix.accounts
+                    .iter()
+                    .find(|(_, acc)| acc.program.is_some_and(|p| kinds.contains(&p)))
+                    .map(|(name, _)| name.clone())
+                    .unwrap_or_default()
+            };
+            let system_program = program(&[config::ProgramKind::System]);
+            let token_program =
+                program(&[config::ProgramKind::Token, config::ProgramKind::Token2022]);
+            let associated_token_program = program(&[config::ProgramKind::AssociatedToken]);
+            let voucher = ix.voucher.as_ref().map(|voucher| {
+                let (account, field) = voucher.signer();
+                Voucher {
+                    account: account.to_string(),
+                    field: field.map(str::to_string),
+                    instructions: ix
+                        .accounts
                         .iter()
-                        .find(|acc| acc.name.as_str() == &ty)
-                        .as_ref()
-                        .expect("account type missing")
-                        .discriminator
-                        .as_ref()
-                        .map(|x| x.size)
-                        .unwrap_or(4), // TODO: fix
-                    name: ty,
-                }),
-            })
-            .collect(),
-            args: ix
-                .args
-                .iter()
-                .map(|field| Field {
-                    name: field.0.to_string(),
-                    ty: Type::from(field.1.clone()),
+                        .find(|(_, acc)| acc.sysvar == Some(config::SysvarKind::Instructions))
+                        .map(|(name, _)| name.clone())
+                        .unwrap_or_default(),
+                }
+            });
+
+            Instruction {
+                id: ix.id as u8,
+                name,
+                accounts: {
+                    let mut sorted = ix.accounts.into_iter().map(|x| x).collect::<Vec<_>>();
+                    sorted.sort_by(|a, b| a.1.id.cmp(&b.1.id));
+                    sorted
+                }
+                .into_iter()
+                .map(|(name, acc)| {
+                    let token = acc.token_kind().map(|kind| match &acc.ata {
+                        Some(ata) => TokenAccount {
+                            kind,
+                            mint: Some(ata.mint.clone()),
+                            authority: Some(ata.owner.clone()),
+                            ata: Some(AssociatedTokenAccount {
+                                init_if_needed: ata.payer.clone().map(|payer| AtaInit {
+                                    payer,
+                                    system_program: system_program.clone(),
+                                    token_program: token_program.clone(),
+                                    associated_token_program: associated_token_program.clone(),
+                                }),
+                            }),
+                        },
+                        None => TokenAccount {
+                            kind,
+                            mint: acc.mint.clone(),
+                            authority: acc.authority.clone(),
+                            ata: None,
+                        },
+                    });
+                    InstructionAccount {
+                        id: acc.id as u8,
+                        name,
+                        owner: match acc.owner {
+                            Some(config::AccountOwner::System) => Some(AccountOwner::System),
+                            Some(config::AccountOwner::Address(address)) => {
+                                Some(AccountOwner::Key(address.0))
+                            }
+                            None if acc.r#type.is_some()
+                                && token.is_none()
+                                && acc.create.is_none() =>
+                            {
+                                Some(AccountOwner::Program)
+                            }
+                            None => None,
+                        },
+                        known: match (acc.program, acc.sysvar) {
+                            (Some(program), _) => Some(KnownAccount::Program(program.into())),
+                            (None, Some(sysvar)) => Some(KnownAccount::Sysvar(sysvar.into())),
+                            (None, None) => None,
+                        },
+                        state: match (acc.create.is_some(), acc.init_if_needed, acc.mutable) {
+                            (true, _, _) => IxAccountState::Create,
+                            (false, true, _) => IxAccountState::InitIfNeeded,
+                            (false, false, true) => IxAccountState::Mutable,
+                            (false, false, false) => IxAccountState::Immutable,
+                        },
+                        is_signed: acc.signed || acc.create == Some(config::CreateKind::Keypair),
+                        optional: acc.optional,
+                        allow_duplicate: acc.allow_duplicate,
+                        manual_apply: acc.manual_apply,
+                        rent_exempt: acc.is_written_program_account()
+                            && acc.rent_exempt.unwrap_or(true),
+                        seed: acc.seed.and_then(|seed| {
+                            let ty = acc.r#type.as_ref()?;
+                            let inputs = seed_inputs.get(ty)?;
+                            let account_seed = accounts
+                                .iter()
+                                .find(|account| &account.name == ty)?
+                                .seed
+                                .as_ref()?;
+                            Some(AccountSeed {
+                                bump: account_seed.bump,
+                                seeds: account_seed
+                                    .seeds
+                                    .iter()
+                                    .map(|s| match s {
+                                        Seed::Defined(s) => Seed::Defined(s.clone()),
+                                        Seed::Input(input) => Seed::Input(
+                                            inputs
+                                                .iter()
+                                                .position(|i| i == input)
+                                                .map(|i| seed[i].clone())
+                                                .expect("seed input is declared"),
+                                        ),
+                                    })
+                                    .collect(),
+                            })
+                        }),
+                        payload: acc.r#type.filter(|_| token.is_none()).map(account_data),
+                        token,
+                        close: acc.close,
+                        constraints: {
+                            let (expr, error) = acc
+                                .constraint
+                                .map(|constraint| (constraint.expr, constraint.error))
+                                .unzip();
+                            Constraints {
+                                has_one: acc.has_one,
+                                address: acc.address.map(|address| address.0),
+                                expr,
+                                error: error.flatten(),
+                            }
+                        },
+                    }
                 })
                 .collect(),
+                args: ix
+                    .args
+                    .into_iter()
+                    .map(|arg| Field {
+                        name: arg.name,
+                        ty: Type::from(arg.r#type),
+                    })
+                    .collect(),
+                remaining: ix.remaining.map(|remaining| RemainingAccounts {
+                    payload: account_data(remaining.r#type),
+                    is_mutable: remaining.mutable,
+                }),
+                voucher,
+                returns: ix.returns.map(Type::from),
+            }
         })
         .collect();
 
@@ -192,9 +512,59 @@ impl From<config::Config> for MyIdl {
                 patch: value.program.version.2,
             },
             name: value.program.name,
+            program_id: value.program.id.map(|id| id.0),
+            checked_setters: value.program.checked_setters,
             instruction_discriminator_size: value.ix_config.discriminator_size as usize,
             accounts,
             instructions,
+            errors: value
+                .errors
+                .into_iter()
+                .map(|(name, error)| Error {
+                    name,
+                    code: error.code,
+                    msg: error.msg,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<config::ProgramKind> for KnownProgram {
+    fn from(value: config::ProgramKind) -> Self {
+        match value {
+            config::ProgramKind::System => KnownProgram::System,
+            config::ProgramKind::Token => KnownProgram::Token,
+            config::ProgramKind::Token2022 => KnownProgram::Token2022,
+            config::ProgramKind::AssociatedToken => KnownProgram::AssociatedToken,
+        }
+    }
+}
+
+impl From<config::SysvarKind> for KnownSysvar {
+    fn from(value: config::SysvarKind) -> Self {
+        match value {
+            config::SysvarKind::Clock => KnownSysvar::Clock,
+            config::SysvarKind::Rent => KnownSysvar::Rent,
+            config::SysvarKind::EpochSchedule => KnownSysvar::EpochSchedule,
+            config::SysvarKind::Instructions => KnownSysvar::Instructions,
+        }
+    }
+}
+
+impl fmt::Display for KnownAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KnownAccount::Program(KnownProgram::System) => write!(f, "program(system)"),
+            KnownAccount::Program(KnownProgram::Token) => write!(f, "program(token)"),
+            KnownAccount::Program(KnownProgram::Token2022) => write!(f, "program(token_2022)"),
+            KnownAccount::Program(KnownProgram::AssociatedToken) => {
+                write!(f, "program(associated_token)")
+            }
+            KnownAccount::Sysvar(KnownSysvar::Clock) => write!(f, "sysvar(clock)"),
+            KnownAccount::Sysvar(KnownSysvar::Rent) => write!(f, "sysvar(rent)"),
+            KnownAccount::Sysvar(KnownSysvar::EpochSchedule) => write!(f, "sysvar(epoch_schedule)"),
+            KnownAccount::Sysvar(KnownSysvar::Instructions) => write!(f, "sysvar(instructions)"),
         }
     }
 }
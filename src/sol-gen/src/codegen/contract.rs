@@ -2,19 +2,37 @@ use convert_case::{Case, Casing};
 use proc_macro2::TokenStream;
 use quote::quote;
 use sol_gen_common::{
+    config::TokenKind,
     discriminator::{DiscriminatorGen, HashDiscriminatorGen},
     error::SolGenError,
-    my_idl::{Account, InstructionAccount, IxAccountState, MyIdl, Type},
+    my_idl::{
+        Account, AccountOwner, AccountSeed, Instruction, InstructionAccount, IxAccountState,
+        KnownAccount, KnownProgram, KnownSysvar, MyIdl, Seed, TokenAccount, Type,
+    },
 };
+use syn::visit_mut::VisitMut;
 
 use crate::config::Config;
 
 pub fn gen_from_config(config: Config) -> Result<TokenStream, SolGenError> {
-    let idl = config.into();
+    gen_from_idl(&config.into())
+}
+
+pub fn gen_from_idl(idl: &MyIdl) -> Result<TokenStream, SolGenError> {
+    let dispatcher = gen_dispatcher::<HashDiscriminatorGen>(idl)?;
+    let contract = gen_contract(idl);
+    let types = gen_types::<HashDiscriminatorGen>(idl)?;
+    let errors = gen_errors(idl);
+    let cpi = idl.program_id.map(|_| gen_cpi(idl));
+    let program_id = idl.program_id.map(|id| {
+        quote! {
+            pub const ID: Pubkey = [#( #id ),*];
 
-    let dispatcher = gen_dispatcher::<HashDiscriminatorGen>(&idl)?;
-    let contract = gen_contract(&idl);
-    let types = gen_types::<HashDiscriminatorGen>(&idl);
+            pub fn check_id(id: &Pubkey) -> bool {
+                id == &ID
+            }
+        }
+    });
 
     Ok(quote! {
         use core::marker::PhantomData;
@@ -23,9 +41,12 @@ pub fn gen_from_config(config: Config) -> Result<TokenStream, SolGenError> {
         use sol_ez::{account::*, account_info::*, AccountData, AccountDataConfig, DataSize};
         use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
 
+        #program_id
+        #errors
         #types
         #contract
         #dispatcher
+        #cpi
     })
 }
 
@@ -52,44 +73,114 @@ where
         .iter()
         .map(|ix| {
             let discriminator_name = str_to_const_name(&ix.name);
-            let call = {
+            let (call, lazy_call) = {
                 let ix_name = str_to_field_name(&ix.name);
                 let accounts_name = str_to_struct_name(&ix.name, Some("Accounts"));
 
-                match ix.args.len() {
-                    0 => quote! {{
-                        let accounts = #accounts_name::load(accounts)?;
-                        T::#ix_name(program_id, accounts)
-                    }},
+                let (args, handle) = match ix.args.len() {
+                    0 => (None, quote! { T::#ix_name(program_id, &mut ix_accounts) }),
                     1 => {
                         let arg = ix
                             .args
                             .iter()
                             .next()
                             .map(|arg| str_to_field_name(&arg.name));
-                        quote! {{
-                            let accounts = #accounts_name::load(accounts)?;
-                            let #arg = ix_data.deserialize_data()?;
-                            T::#ix_name(program_id, accounts, #arg)
-                        }}
+                        (
+                            Some(quote! { let #arg = ix_data.deserialize_data()?; }),
+                            quote! { T::#ix_name(program_id, &mut ix_accounts, #arg) },
+                        )
                     }
                     _ => {
                         let args = ix.args.iter().map(|arg| str_to_field_name(&arg.name));
                         let args2 = args.clone();
-                        quote! {{
-                            let accounts = #accounts_name::load(accounts)?;
-                            let ( #( #args ),* ) = ix_data.deserialize_data()?;
-                            T::#ix_name(program_id, accounts, #( #args2 ),* )
-                        }}
+                        (
+                            Some(quote! { let ( #( #args ),* ) = ix_data.deserialize_data()?; }),
+                            quote! { T::#ix_name(program_id, &mut ix_accounts, #( #args2 ),* ) },
+                        )
                     }
-                }
+                };
+                // changed accounts are only applied, checked and closed once
+                // the handler succeeded
+                let exit = has_exit(ix).then(|| quote! { ix_accounts.exit(accounts) });
+                let handle = match (&ix.returns, exit) {
+                    (Some(_), exit) => {
+                        let exit = exit.map(|exit| quote! { #exit?; });
+                        quote! {
+                            let value = #handle?;
+                            #exit
+                            sol_ez::cpi::set_return_data(&value)
+                        }
+                    }
+                    (None, Some(exit)) => quote! {
+                        #handle?;
+                        #exit
+                    },
+                    (None, None) => handle,
+                };
+
+                let voucher = ix.voucher.as_ref().map(|voucher| {
+                    let instructions = str_to_field_name(&voucher.instructions);
+                    let account = str_to_field_name(&voucher.account);
+                    let signer = match &voucher.field {
+                        Some(field) => {
+                            let field = str_to_field_name(field);
+                            quote! { &ix_accounts.#account.as_ref().#field }
+                        }
+                        None => quote! { ix_accounts.#account.key() },
+                    };
+                    quote! {
+                        sol_ez::precompile::verify_ed25519(&ix_accounts.#instructions, #signer, payload)?;
+                    }
+                });
+
+                let body = quote! {
+                    #voucher
+                    #args
+                    #handle
+                };
+
+                // the lazy entrypoint only parses the accounts the instruction
+                // declares, and its remaining accounts as they are iterated
+                let accounts_len = ix.accounts.len();
+                let uninit = quote! {
+                    const { core::mem::MaybeUninit::<pinocchio::account_info::AccountInfo>::uninit() }
+                };
+                let lazy_load = match &ix.remaining {
+                    Some(_) => quote! {
+                        let mut parsed = [#uninit; pinocchio::MAX_TX_ACCOUNTS];
+                        let (fixed, rest) = parsed.split_at_mut(#accounts_len);
+                        let accounts = sol_ez::entrypoint::parse_accounts(&mut context, fixed)?;
+                        let remaining = RemainingAccounts::lazy(context, accounts, rest, program_id);
+                        let mut ix_accounts =
+                            #accounts_name::load_with_remaining(program_id, accounts, remaining)?;
+                    },
+                    None => quote! {
+                        let mut parsed = [#uninit; #accounts_len];
+                        let accounts = sol_ez::entrypoint::parse_accounts(&mut context, &mut parsed)?;
+                        let mut ix_accounts = #accounts_name::load(program_id, accounts)?;
+                    },
+                };
+
+                (
+                    quote! {{
+                        let mut ix_accounts = #accounts_name::load(program_id, accounts)?;
+                        #body
+                    }},
+                    quote! {{
+                        #lazy_load
+                        #body
+                    }},
+                )
             };
 
-            Ok(quote! {
-                &#discriminator_name => #call
-            })
+            Ok((
+                quote! { #discriminator_name => #call },
+                quote! { #discriminator_name => #lazy_call },
+            ))
         })
         .collect::<Result<Vec<_>, SolGenError>>()?;
+    let (ix_match_branch, lazy_ix_match_branch): (Vec<_>, Vec<_>) =
+        ix_match_branch.into_iter().unzip();
 
     Ok(quote! {
         pub struct #dispatcher_name<T> {
@@ -108,12 +199,23 @@ where
                 payload: &[u8]
             ) -> Result<(), ProgramError> {
                 let ix_data = sol_ez::InstructionData::new(payload)?;
-                match ix_data.ix {
+                match *ix_data.ix {
                     #( #ix_match_branch, )*
                     _ => Err(ProgramError::InvalidInstructionData)
                 }
             }
 
+            fn dispatch_lazy(
+                mut context: pinocchio::entrypoint::InstructionContext
+            ) -> Result<(), ProgramError> {
+                // unknown instructions are rejected before parsing any account
+                let (program_id, payload) = sol_ez::entrypoint::peek_instruction(&context)?;
+                let ix_data = sol_ez::InstructionData::new(payload)?;
+                match *ix_data.ix {
+                    #( #lazy_ix_match_branch, )*
+                    _ => Err(ProgramError::InvalidInstructionData)
+                }
+            }
         }
     })
 }
@@ -123,10 +225,13 @@ pub fn gen_contract(idl: &MyIdl) -> TokenStream {
     let contract_ix_fns = idl.instructions.iter().map(|ix| {
         let fn_name = str_to_field_name(&ix.name);
         let accounts_name = str_to_struct_name(&ix.name, Some("Accounts"));
+        let returns = gen_returns(ix);
+        let docs = gen_handler_docs(ix);
 
         if ix.args.len() == 0 {
             return quote! {
-                fn #fn_name(program_id: &Pubkey, accounts: #accounts_name) -> Result<(), ProgramError>
+                #docs
+                fn #fn_name(program_id: &Pubkey, accounts: &mut #accounts_name) -> Result<#returns, ProgramError>
             };
         }
 
@@ -137,7 +242,8 @@ pub fn gen_contract(idl: &MyIdl) -> TokenStream {
         });
 
         quote! {
-            fn #fn_name(program_id: &Pubkey, accounts: #accounts_name #(, #args )*) -> Result<(), ProgramError>
+            #docs
+            fn #fn_name(program_id: &Pubkey, accounts: &mut #accounts_name #(, #args )*) -> Result<#returns, ProgramError>
         }
     });
 
@@ -148,7 +254,42 @@ pub fn gen_contract(idl: &MyIdl) -> TokenStream {
     }
 }
 
-pub fn gen_types<D>(idl: &MyIdl) -> TokenStream
+/// Documents the accounts of a handler the dispatcher does not apply, whose
+/// changes are dropped unless the handler applies them itself.
+fn gen_handler_docs(ix: &Instruction) -> TokenStream {
+    let manual_apply = ix
+        .accounts
+        .iter()
+        .filter(|acc| acc.manual_apply)
+        .map(|acc| format!("`{}`", acc.name))
+        .collect::<Vec<_>>();
+    let mut docs = Vec::new();
+    if !manual_apply.is_empty() {
+        docs.push(format!(
+            " {} must be applied by the handler, see `manual_apply`.",
+            manual_apply.join(", ")
+        ));
+    }
+    if ix.accounts.iter().any(|acc| acc.state.is_create()) {
+        docs.push(
+            " Accounts created with `init` or `init_keypair` are only written by their `apply`."
+                .to_string(),
+        );
+    }
+    if ix
+        .remaining
+        .as_ref()
+        .is_some_and(|remaining| remaining.is_mutable)
+    {
+        docs.push(
+            " The remaining accounts are only written by their `apply`, see `update_each`."
+                .to_string(),
+        );
+    }
+    quote! { #( #[doc = #docs] )* }
+}
+
+pub fn gen_types<D>(idl: &MyIdl) -> Result<TokenStream, SolGenError>
 where
     D: DiscriminatorGen,
     D::Seed: quote::ToTokens,
@@ -156,97 +297,875 @@ where
     let account_types = idl
         .accounts
         .iter()
-        .map(|acc| gen_account::<D>(&idl.name, acc));
+        .map(|acc| gen_account::<D>(&idl.name, acc, idl.checked_setters));
     let accounts_types = idl
         .instructions
         .iter()
-        .map(|ix| gen_accounts(&ix.name, &ix.accounts));
+        .map(|ix| gen_accounts(&idl.name, ix))
+        .collect::<Result<Vec<_>, SolGenError>>()?;
 
-    quote! {
+    Ok(quote! {
         #( #account_types )*
         #( #accounts_types )*
+    })
+}
+
+/// Generates the error enum of the program, returned by failing account
+/// constraints.
+fn gen_errors(idl: &MyIdl) -> Option<TokenStream> {
+    if idl.errors.is_empty() {
+        return None;
     }
+    let error_name = str_to_struct_name(&idl.name, Some("Error"));
+    let variants = idl.errors.iter().map(|error| {
+        let name = str_to_struct_name(&error.name, None);
+        let code = error.code;
+        let doc = error.msg.as_ref().map(|msg| {
+            let msg = format!(" {}", msg);
+            quote! { #[doc = #msg] }
+        });
+        quote! {
+            #doc
+            #name = #code
+        }
+    });
+
+    Some(quote! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u32)]
+        pub enum #error_name {
+            #( #variants, )*
+        }
+
+        impl From<#error_name> for ProgramError {
+            fn from(value: #error_name) -> Self {
+                ProgramError::Custom(value as u32)
+            }
+        }
+    })
 }
 
-fn gen_accounts(ix_name: &str, accounts: &[InstructionAccount]) -> TokenStream {
+fn gen_accounts(program_name: &str, ix: &Instruction) -> Result<TokenStream, SolGenError> {
+    let ix_name = ix.name.as_str();
+    let accounts = ix.accounts.as_slice();
     let accounts_name = str_to_struct_name(ix_name, Some("Accounts"));
     let accounts_fields = accounts.iter().map(|acc| {
         let field_name = str_to_field_name(&acc.name);
-        let account_type = acc
-            .payload
-            .as_ref()
-            .map(|p| 
-                { 
-                    let size = p.discriminator_size as usize;
-                    let name = str_to_struct_name(&p.name, None);
-                    quote! { AccountData<#size,#name> }
-                }, 
-            )
-            .unwrap_or_else(|| { let name = str_to_struct_name("empty", None); quote!{ #name}});
+        let field_type = gen_ix_account_type(acc);
+        if acc.optional {
+            quote! { pub #field_name: Option<#field_type> }
+        } else {
+            quote! { pub #field_name: #field_type }
+        }
+    });
+    let remaining_type = ix.remaining.as_ref().map(|remaining| {
+        let size = remaining.payload.discriminator_size as usize;
+        let name = str_to_struct_name(&remaining.payload.name, None);
+        let state = if remaining.is_mutable {
+            quote! { Mutable }
+        } else {
+            quote! { Immutable }
+        };
+        quote! { RemainingAccounts<'info, #size, #name, #state> }
+    });
+    let remaining_field = remaining_type
+        .as_ref()
+        .map(|remaining_type| quote! { pub remaining: #remaining_type, });
 
-        
+    // accounts created with init_if_needed are loaded last, as their creation
+    // borrows the other accounts
+    let ata_inits = accounts
+        .iter()
+        .filter_map(|acc| {
+            let init = acc.token.as_ref()?.ata.as_ref()?.init_if_needed.as_ref()?;
+            Some((acc, init))
+        })
+        .collect::<Vec<_>>();
+    let mutable_bindings = ata_inits
+        .iter()
+        .flat_map(|(acc, init)| {
+            let token = acc.token.as_ref().expect("ata is a token account");
+            [
+                token.mint.as_ref(),
+                token
+                    .authority
+                    .as_ref()
+                    .filter(|owner| **owner != init.payer),
+                Some(&init.payer),
+                Some(&init.system_program),
+                Some(&init.token_program),
+            ]
+        })
+        .flatten()
+        .collect::<Vec<_>>();
 
-        let account_state = match (acc.state, acc.is_signed) {
-            (IxAccountState::Create, _) => {
-                return  quote! { pub #field_name: Account<'info, PhantomData<#account_type>, Init, Unsigned> };
-            },
-            (IxAccountState::Immutable, true) => str_to_struct_name("AccountReadOnlySigned", None),
-            (IxAccountState::Immutable, false) => str_to_struct_name("AccountReadOnly", None),
-            (IxAccountState::Mutable, true) => str_to_struct_name("AccountWritableSigned", None),
-            (IxAccountState::Mutable, false) => str_to_struct_name("AccountWritable", None),
-        };
+    let load = accounts
+        .iter()
+        .filter(|acc| !ata_inits.iter().any(|(init, _)| init.id == acc.id))
+        .map(|acc| {
+            let field_name = str_to_field_name(&acc.name);
+            let binding = if mutable_bindings.contains(&&acc.name) {
+                quote! { mut #field_name }
+            } else {
+                quote! { #field_name }
+            };
+            let id = acc.id as usize;
+            let info = quote! { accounts.get(#id).ok_or(ProgramError::NotEnoughAccountKeys)? };
+            // an optional account is loaded from a local, as it is only
+            // loaded when it is not the program id placeholder
+            let account = if acc.optional {
+                quote! { account }
+            } else {
+                info.clone()
+            };
 
-        quote! { pub #field_name: #account_state<'info, #account_type> }
-    });
+            let load = if let Some(known) = acc.known {
+                let known_type = match known {
+                    KnownAccount::Program(_) => quote! { Program },
+                    KnownAccount::Sysvar(_) => quote! { Sysvar },
+                };
+                quote! { #known_type::new(#account)? }
+            } else if acc.state == IxAccountState::InitIfNeeded {
+                let payload = acc
+                    .payload
+                    .as_ref()
+                    .expect("init_if_needed account has a type");
+                let size = payload.discriminator_size as usize;
+                let name = str_to_struct_name(&payload.name, None);
+                quote! { InitIfNeeded::<#size, #name>::load(#account, program_id)? }
+            } else if acc.state.is_create() && acc.is_signed {
+                quote! { Account::new_init(AccountInfo::new_init_signed(#account)?) }
+            } else if acc.state.is_create() {
+                quote! { Account::new_init(AccountInfo::new_init(#account)?) }
+            } else {
+                let mut code = quote! { AccountBuilder::new(#account) };
+                match &acc.owner {
+                    Some(AccountOwner::Program) => code.extend(quote! { .owner(program_id)? }),
+                    Some(AccountOwner::System) => {
+                        code.extend(quote! { .owner(&sol_ez::SYSTEM_PROGRAM_ID)? })
+                    }
+                    Some(AccountOwner::Key(key)) => code.extend(quote! { .owner(&[#( #key ),*])? }),
+                    None => {}
+                }
+                if let Some(token) = &acc.token {
+                    let token_type = gen_token_type(token.kind);
+                    code.extend(quote! { .token_payload::<#token_type>() });
+                } else if let Some(payload) = &acc.payload {
+                    // the payload type is named for constraints on it to
+                    // typecheck before the accounts are returned
+                    let size = payload.discriminator_size as usize;
+                    let name = str_to_struct_name(&payload.name, None);
+                    code.extend(quote! { .set_payload::<#size, #name>() });
+                }
+                if acc.state.is_mutable() {
+                    code.extend(quote! { .mutable()? });
+                }
+                if acc.allow_duplicate {
+                    code.extend(quote! { .allow_duplicate() });
+                }
+                if acc.is_signed {
+                    code.extend(quote! { .signed()? });
+                }
+                code.extend(quote! { .build()? });
+                code
+            };
 
-    let load = accounts.iter().map(|acc| {
+            if acc.optional {
+                quote! {
+                    let #binding = match #info {
+                        account if account.key() == program_id => None,
+                        account => Some(#load),
+                    };
+                }
+            } else {
+                quote! { let #binding = #load; }
+            }
+        });
+    // the address of an associated token account is checked before the other
+    // accounts, and it is only created once they were
+    let ata_addresses = ata_inits.iter().map(|(acc, init)| {
         let field_name = str_to_field_name(&acc.name);
         let id = acc.id as usize;
+        let token = acc.token.as_ref().expect("ata is a token account");
+        let mint = token.mint.as_deref().map(str_to_field_name);
+        let owner = str_to_field_name(token.authority.as_deref().expect("ata has an owner"));
+        let token_program = str_to_field_name(&init.token_program);
+        quote! {
+            let #field_name = sol_ez::token::AssociatedTokenAccount::new(
+                accounts.get(#id).ok_or(ProgramError::NotEnoughAccountKeys)?,
+                #owner.key(),
+                #mint.key(),
+                &#token_program,
+            )?;
+        }
+    });
+    let load_ata_inits = ata_inits.iter().map(|(acc, init)| {
+        let field_name = str_to_field_name(&acc.name);
+        let token = acc.token.as_ref().expect("ata is a token account");
+        let mint = token.mint.as_deref().map(str_to_field_name);
+        let owner = token.authority.as_deref().expect("ata has an owner");
+        let payer = str_to_field_name(&init.payer);
+        let system_program = str_to_field_name(&init.system_program);
+        let token_program = str_to_field_name(&init.token_program);
+        let associated_token_program = str_to_field_name(&init.associated_token_program);
+        let (init_fn, owner) = if owner == init.payer {
+            (quote! { init_for_payer }, None)
+        } else {
+            let owner = str_to_field_name(owner);
+            (quote! { init }, Some(quote! { &mut #owner, }))
+        };
+        quote! {
+            let #field_name = #field_name.#init_fn(
+                #owner
+                &mut #mint,
+                &mut #payer,
+                &mut #system_program,
+                &mut #token_program,
+                &#associated_token_program,
+            )?;
+        }
+    });
+    let (ata_token_checks, token_checks): (Vec<_>, Vec<_>) = accounts
+        .iter()
+        .filter_map(|acc| {
+            let token = acc.token.as_ref()?;
+            Some((acc, gen_loaded_checks(acc, gen_token_checks(acc, token))))
+        })
+        .partition(|(acc, _)| ata_inits.iter().any(|(init, _)| init.id == acc.id));
+    let ata_token_checks = ata_token_checks.into_iter().map(|(_, checks)| checks);
+    let token_checks = token_checks.into_iter().map(|(_, checks)| checks);
+    // created accounts are checked against the seeds they are created with
+    let seed_checks = accounts
+        .iter()
+        .filter(|acc| {
+            matches!(
+                acc.state,
+                IxAccountState::Mutable | IxAccountState::Immutable
+            )
+        })
+        .filter_map(|acc| {
+            Some(gen_loaded_checks(
+                acc,
+                gen_seed_check(acc, acc.seed.as_ref()?),
+            ))
+        })
+        .collect::<Vec<_>>();
+    let created = ata_inits.iter().map(|(acc, _)| *acc).collect::<Vec<_>>();
+    let (constraint_checks, ata_constraint_checks): (Vec<_>, Vec<_>) = accounts
+        .iter()
+        .filter(|acc| !acc.constraints.is_empty())
+        .map(|acc| gen_constraint_checks(program_name, ix_name, acc, accounts, &created))
+        .collect::<Result<Vec<_>, SolGenError>>()?
+        .into_iter()
+        .unzip();
+    // constraint expressions are negated as written
+    let allow_nonminimal_bool = accounts
+        .iter()
+        .any(|acc| acc.constraints.expr.is_some())
+        .then(|| quote! { #[allow(clippy::nonminimal_bool)] });
+    // mutable accounts are checked against every other account before any
+    // is loaded, as loading a duplicate fails on its lock without naming it
+    let (mutable, others): (Vec<_>, Vec<_>) = accounts
+        .iter()
+        .filter(|acc| acc.known.is_none() && !acc.allow_duplicate)
+        .map(|acc| {
+            let id = acc.id as usize;
+            let name = &acc.name;
+            (
+                acc.state != IxAccountState::Immutable,
+                quote! { (#id, #name) },
+            )
+        })
+        .partition(|(is_mutable, _)| *is_mutable);
+    let check_unique = (!mutable.is_empty() && mutable.len() + others.len() > 1).then(|| {
+        let mutable = mutable.iter().map(|(_, acc)| acc);
+        let others = others.iter().map(|(_, acc)| acc);
+        quote! {
+            sol_ez::account::check_unique(
+                program_id,
+                accounts,
+                &[#( #mutable ),*],
+                &[#( #others ),*],
+            )?;
+        }
+    });
+    let fields = accounts
+        .iter()
+        .map(|acc| str_to_field_name(&acc.name))
+        .chain(
+            ix.remaining
+                .as_ref()
+                .map(|_| str_to_field_name("remaining")),
+        );
 
-        if acc.state.is_create() {
+    let program_id = if check_unique.is_some()
+        || !seed_checks.is_empty()
+        || accounts
+            .iter()
+            .any(|acc| acc.optional || matches!(acc.owner, Some(AccountOwner::Program)))
+    {
+        quote! { program_id }
+    } else {
+        quote! { _program_id }
+    };
+
+    let closes = accounts
+        .iter()
+        .filter_map(|acc| {
+            let destination = accounts
+                .iter()
+                .find(|dest| Some(&dest.name) == acc.close.as_ref())?;
+            let size = acc.payload.as_ref()?.discriminator_size as usize;
+            let id = acc.id as usize;
+            let destination_id = destination.id as usize;
+            Some(quote! {
+                sol_ez::account::close_account::<#size>(
+                    accounts.get(#id).ok_or(ProgramError::NotEnoughAccountKeys)?,
+                    accounts.get(#destination_id).ok_or(ProgramError::NotEnoughAccountKeys)?,
+                )?;
+            })
+        })
+        .collect::<Vec<_>>();
+    let rent_checks = accounts
+        .iter()
+        .filter(|acc| acc.rent_exempt)
+        .map(|acc| {
+            let id = acc.id as usize;
             quote! {
-                #field_name: Account::new_init(
-                    AccountInfo::new_init(
-                        accounts.get(#id).ok_or(ProgramError::NotEnoughAccountKeys)?
-                    )?
-                )
+                sol_ez::account::check_rent_exempt(
+                    accounts.get(#id).ok_or(ProgramError::NotEnoughAccountKeys)?,
+                )?;
             }
-        } else {
-            let mut code = quote! {
-                #field_name: AccountBuilder::new(
-                    accounts.get(#id).ok_or(ProgramError::NotEnoughAccountKeys)?
-                )
-            };
-            if acc.payload.is_some() {
-                code.extend(quote! { .set_payload() });
+        })
+        .collect::<Vec<_>>();
+    // the handler may take lamports from any of the mutable remaining accounts
+    let remaining_rent_check = ix
+        .remaining
+        .as_ref()
+        .filter(|remaining| remaining.is_mutable)
+        .map(|_| {
+            quote! {
+                for account in remaining {
+                    sol_ez::account::check_rent_exempt(account)?;
+                }
+            }
+        });
+    let applies = applied_accounts(ix)
+        .map(|acc| {
+            let field_name = str_to_field_name(&acc.name);
+            let apply = quote! { #field_name.apply()?; };
+            if acc.optional {
+                quote! {
+                    if let Some(#field_name) = &mut self.#field_name {
+                        #apply
+                    }
+                }
+            } else if acc.state == IxAccountState::InitIfNeeded {
+                quote! {
+                    if let InitIfNeeded::Initialized(#field_name) = &mut self.#field_name {
+                        #apply
+                    }
+                }
+            } else {
+                quote! { self.#field_name.apply()?; }
             }
-            if acc.state.is_mutable() {
-                code.extend(quote! { .mutable()? });
+        })
+        .collect::<Vec<_>>();
+    // the handler must have applied its manual_apply accounts once it
+    // succeeded, or their changes would be silently dropped
+    let unapplied_checks = ix
+        .accounts
+        .iter()
+        .filter(|acc| acc.manual_apply)
+        .map(|acc| {
+            let field_name = str_to_field_name(&acc.name);
+            let message = format!("account {} changed but never applied", acc.name);
+            let dirty = if acc.optional {
+                quote! { self.#field_name.as_ref().is_some_and(|account| account.is_dirty()) }
+            } else {
+                quote! { self.#field_name.is_dirty() }
+            };
+            quote! {
+                #[cfg(debug_assertions)]
+                if #dirty {
+                    panic!(#message);
+                }
             }
-            if acc.is_signed {
-                code.extend(quote! { .signed()? });
+        })
+        .collect::<Vec<_>>();
+    let unapplied_doc = (!unapplied_checks.is_empty()).then(|| {
+        quote! {
+            ///
+            /// Panics in debug builds if the handler changed a `manual_apply`
+            /// account without applying it.
+        }
+    });
+    let exit = has_exit(ix).then(|| {
+        let mutability = (!applies.is_empty()).then(|| quote! { mut });
+        // the accounts are released before they are checked and closed
+        let release = (!rent_checks.is_empty()
+            || remaining_rent_check.is_some()
+            || !closes.is_empty())
+        .then(|| {
+            quote! { drop(self); }
+        });
+        let accounts_param = if rent_checks.is_empty() && closes.is_empty() {
+            quote! { _accounts }
+        } else {
+            quote! { accounts }
+        };
+        // only the iterated remaining accounts can have been changed
+        let iterated = remaining_rent_check
+            .as_ref()
+            .map(|_| quote! { let remaining = self.remaining.iterated(); });
+        quote! {
+            /// Applies the changed accounts, then checks the written accounts
+            /// are rent exempt and closes the closed accounts, once the
+            /// handler succeeded.
+            #unapplied_doc
+            pub fn exit(
+                #mutability self,
+                #accounts_param: &'info [pinocchio::account_info::AccountInfo]
+            ) -> Result<(), ProgramError> {
+                #( #unapplied_checks )*
+                #( #applies )*
+                #iterated
+                #release
+                #( #rent_checks )*
+                #remaining_rent_check
+                #( #closes )*
+                Ok(())
             }
-            code.extend(quote! { .build()? });
-            code
         }
     });
 
-    quote! {
+    let load_fn = match &remaining_type {
+        Some(remaining_type) => {
+            let offset = accounts.len();
+            quote! {
+                pub fn load(
+                    program_id: &Pubkey,
+                    accounts: &'info[pinocchio::account_info::AccountInfo]
+                ) -> Result<Self, ProgramError> {
+                    let remaining = RemainingAccounts::new(accounts, #offset, program_id);
+                    Self::load_with_remaining(program_id, accounts, remaining)
+                }
+
+                /// Loads the accounts of the instruction, with its remaining
+                /// accounts parsed by the entrypoint or as they are iterated.
+                #allow_nonminimal_bool
+                pub fn load_with_remaining(
+                    #program_id: &Pubkey,
+                    accounts: &'info[pinocchio::account_info::AccountInfo],
+                    remaining: #remaining_type,
+                ) -> Result<Self, ProgramError> {
+                    #check_unique
+                    #( #load )*
+                    #( #ata_addresses )*
+                    #( #seed_checks )*
+                    #( #token_checks )*
+                    #( #constraint_checks )*
+                    #( #load_ata_inits )*
+                    #( #ata_token_checks )*
+                    #( #ata_constraint_checks )*
+                    Ok(Self {
+                        #( #fields, )*
+                    })
+                }
+            }
+        }
+        None => quote! {
+            #allow_nonminimal_bool
+            pub fn load(
+                #program_id: &Pubkey,
+                accounts: &'info[pinocchio::account_info::AccountInfo]
+            ) -> Result<Self, ProgramError> {
+                #check_unique
+                #( #load )*
+                #( #ata_addresses )*
+                #( #seed_checks )*
+                #( #token_checks )*
+                #( #constraint_checks )*
+                #( #load_ata_inits )*
+                #( #ata_token_checks )*
+                #( #ata_constraint_checks )*
+                Ok(Self {
+                    #( #fields, )*
+                })
+            }
+        },
+    };
+
+    Ok(quote! {
         pub struct #accounts_name<'info> {
             #( #accounts_fields, )*
+            #remaining_field
         }
 
         impl<'info> #accounts_name<'info> {
-            pub fn load(accounts: &'info[pinocchio::account_info::AccountInfo]) -> Result<Self, ProgramError> {
-                Ok(Self {
-                    #( #load, )*
-                })
+            #load_fn
+
+            #exit
+        }
+    })
+}
+
+/// The accounts of an instruction the dispatcher applies once the handler
+/// succeeded: mutable program accounts that aren't closed or applied by the
+/// handler itself.
+fn applied_accounts(ix: &Instruction) -> impl Iterator<Item = &InstructionAccount> {
+    ix.accounts.iter().filter(|acc| {
+        matches!(
+            acc.state,
+            IxAccountState::Mutable | IxAccountState::InitIfNeeded
+        ) && acc.known.is_none()
+            && acc.token.is_none()
+            && acc.payload.is_some()
+            && acc.close.is_none()
+            && !acc.manual_apply
+    })
+}
+
+/// Whether the accounts of an instruction have an `exit` to call once the
+/// handler succeeded.
+fn has_exit(ix: &Instruction) -> bool {
+    applied_accounts(ix).next().is_some()
+        || ix.accounts.iter().any(|acc| acc.manual_apply)
+        || ix
+            .remaining
+            .as_ref()
+            .is_some_and(|remaining| remaining.is_mutable)
+        || ix
+            .accounts
+            .iter()
+            .any(|acc| acc.rent_exempt || acc.close.is_some())
+}
+
+/// The type of the field an instruction account is loaded into.
+fn gen_ix_account_type(acc: &InstructionAccount) -> TokenStream {
+    if let Some(known) = acc.known {
+        return gen_known_account(known);
+    }
+
+    let account_type = if let Some(token) = &acc.token {
+        gen_token_type(token.kind)
+    } else {
+        acc.payload
+            .as_ref()
+            .map(|p| {
+                let size = p.discriminator_size as usize;
+                let name = str_to_struct_name(&p.name, None);
+                quote! { AccountData<#size,#name> }
+            })
+            .unwrap_or_else(|| {
+                let name = str_to_struct_name("empty", None);
+                quote! { #name }
+            })
+    };
+
+    let account_state = match (acc.state, acc.is_signed) {
+        (IxAccountState::Create, true) => {
+            return quote! { Account<'info, PhantomData<#account_type>, Init, Signed> };
+        }
+        (IxAccountState::Create, false) => {
+            return quote! { Account<'info, PhantomData<#account_type>, Init, Unsigned> };
+        }
+        (IxAccountState::InitIfNeeded, _) => {
+            let payload = acc
+                .payload
+                .as_ref()
+                .expect("init_if_needed account has a type");
+            let size = payload.discriminator_size as usize;
+            let name = str_to_struct_name(&payload.name, None);
+            return quote! { InitIfNeeded<'info, #size, #name> };
+        }
+        (IxAccountState::Immutable, true) => str_to_struct_name("AccountReadOnlySigned", None),
+        (IxAccountState::Immutable, false) => str_to_struct_name("AccountReadOnly", None),
+        (IxAccountState::Mutable, true) => str_to_struct_name("AccountWritableSigned", None),
+        (IxAccountState::Mutable, false) => str_to_struct_name("AccountWritable", None),
+    };
+
+    quote! { #account_state<'info, #account_type> }
+}
+
+fn gen_known_account(known: KnownAccount) -> TokenStream {
+    match known {
+        KnownAccount::Program(KnownProgram::System) => quote! { Program<'info, System> },
+        KnownAccount::Program(KnownProgram::Token) => quote! { Program<'info, Token> },
+        KnownAccount::Program(KnownProgram::Token2022) => quote! { Program<'info, Token2022> },
+        KnownAccount::Program(KnownProgram::AssociatedToken) => {
+            quote! { Program<'info, AssociatedToken> }
+        }
+        KnownAccount::Sysvar(KnownSysvar::Clock) => quote! { Sysvar<'info, Clock> },
+        KnownAccount::Sysvar(KnownSysvar::Rent) => quote! { Sysvar<'info, Rent> },
+        KnownAccount::Sysvar(KnownSysvar::EpochSchedule) => quote! { Sysvar<'info, EpochSchedule> },
+        KnownAccount::Sysvar(KnownSysvar::Instructions) => quote! { Sysvar<'info, Instructions> },
+    }
+}
+
+fn gen_token_type(kind: TokenKind) -> TokenStream {
+    match kind {
+        TokenKind::Account => quote! { sol_ez::token::TokenAccount },
+        TokenKind::Mint => quote! { sol_ez::token::Mint },
+    }
+}
+
+/// Generates the checks of a token account against its mint and authority
+/// accounts, run once every account is loaded.
+fn gen_token_checks(acc: &InstructionAccount, token: &TokenAccount) -> TokenStream {
+    let field_name = str_to_field_name(&acc.name);
+    let mint = token.mint.as_ref().map(|mint| {
+        let mint = str_to_field_name(mint);
+        quote! {
+            if #field_name.as_ref().mint != *#mint.key() {
+                return Err(ProgramError::InvalidAccountData);
             }
         }
+    });
+    let authority = token.authority.as_ref().map(|authority| {
+        let authority = str_to_field_name(authority);
+        match token.kind {
+            TokenKind::Account => quote! {
+                if #field_name.as_ref().owner != *#authority.key() {
+                    return Err(ProgramError::IllegalOwner);
+                }
+            },
+            TokenKind::Mint => quote! {
+                if #field_name.as_ref().mint_authority != Some(*#authority.key()) {
+                    return Err(ProgramError::IllegalOwner);
+                }
+            },
+        }
+    });
+
+    // accounts created with init_if_needed have their address checked
+    // before they are created
+    let ata = token
+        .ata
+        .as_ref()
+        .filter(|ata| ata.init_if_needed.is_none())
+        .map(|_| {
+            let mint = token.mint.as_deref().map(str_to_field_name);
+            let owner = token.authority.as_deref().map(str_to_field_name);
+            quote! {
+                sol_ez::token::check_associated_token_address(
+                    #field_name.key(),
+                    #owner.key(),
+                    #mint.key(),
+                    #field_name.owner(),
+                )?;
+            }
+        });
+
+    quote! {
+        #ata
+        #mint
+        #authority
+    }
+}
+
+/// Generates the check that a loaded account is at the address derived from
+/// its seeds and the bump it stores.
+fn gen_seed_check(acc: &InstructionAccount, seed: &AccountSeed) -> TokenStream {
+    let field_name = str_to_field_name(&acc.name);
+    let seeds = seed.seeds.iter().map(|seed| match seed {
+        Seed::Defined(s) => {
+            let s = proc_macro2::Literal::byte_string(s.as_bytes());
+            quote! { #s.as_slice() }
+        }
+        Seed::Input(name) => {
+            let name = str_to_field_name(name);
+            quote! { #name.key().as_slice() }
+        }
+    });
+    quote! {
+        sol_ez::account::check_address(
+            #field_name.key(),
+            &[#( #seeds ),*],
+            #field_name.as_ref().bump,
+            program_id,
+        )?;
     }
 }
 
-fn gen_account<D>(program_name: &str, account: &Account) -> TokenStream
+/// Generates the `has_one`, `address` and `constraint` checks of an account,
+/// run once every account is loaded.
+///
+/// The checks are split in the ones run before the `created` associated token
+/// accounts are, and the ones run after: the checks of a created account, and
+/// the constraint expressions that reference one.
+fn gen_constraint_checks(
+    program_name: &str,
+    ix_name: &str,
+    acc: &InstructionAccount,
+    accounts: &[InstructionAccount],
+    created: &[&InstructionAccount],
+) -> Result<(TokenStream, TokenStream), SolGenError> {
+    let field_name = str_to_field_name(&acc.name);
+
+    let has_one = acc.constraints.has_one.iter().map(|name| {
+        let name = str_to_field_name(name);
+        quote! {
+            if #field_name.as_ref().#name != *#name.key() {
+                return Err(sol_ez::error::ErrorCode::ConstraintHasOne.into());
+            }
+        }
+    });
+    let address = acc.constraints.address.map(|address| {
+        quote! {
+            if #field_name.key() != &[#( #address ),*] {
+                return Err(sol_ez::error::ErrorCode::ConstraintAddress.into());
+            }
+        }
+    });
+    let mut references_created = false;
+    let expr = acc
+        .constraints
+        .expr
+        .as_ref()
+        .map(|expr| {
+            let mut expr = syn::parse_str::<syn::Expr>(expr).map_err(|err| {
+                anyhow::anyhow!(
+                    "id({}) account({}) invalid constraint: {}",
+                    ix_name,
+                    acc.name,
+                    err
+                )
+            })?;
+            let mut payload_access = PayloadAccess::new(acc, accounts);
+            payload_access.visit_expr_mut(&mut expr);
+            references_created = created.iter().any(|created| {
+                payload_access
+                    .referenced
+                    .contains(&str_to_field_name(&created.name))
+            });
+            let error = match &acc.constraints.error {
+                Some(error) => {
+                    let error_name = str_to_struct_name(program_name, Some("Error"));
+                    let variant = str_to_struct_name(error, None);
+                    quote! { #error_name::#variant }
+                }
+                None => quote! { sol_ez::error::ErrorCode::Constraint },
+            };
+            Ok::<_, SolGenError>(quote! {
+                if !(#expr) {
+                    return Err(#error.into());
+                }
+            })
+        })
+        .transpose()?;
+
+    let checks = quote! {
+        #( #has_one )*
+        #address
+    };
+    let (before, after) = if created.iter().any(|created| created.id == acc.id) {
+        (
+            TokenStream::new(),
+            quote! {
+                #checks
+                #expr
+            },
+        )
+    } else if references_created {
+        (checks, expr.unwrap_or_default())
+    } else {
+        (
+            quote! {
+                #checks
+                #expr
+            },
+            TokenStream::new(),
+        )
+    };
+    let wrap = |checks: TokenStream| {
+        if checks.is_empty() {
+            checks
+        } else {
+            gen_loaded_checks(acc, checks)
+        }
+    };
+    Ok((wrap(before), wrap(after)))
+}
+
+/// Wraps the checks of an account to only run once it holds an account, when
+/// an optional account was passed or an init_if_needed account exists.
+fn gen_loaded_checks(acc: &InstructionAccount, checks: TokenStream) -> TokenStream {
+    let field_name = str_to_field_name(&acc.name);
+    if acc.optional {
+        quote! {
+            if let Some(#field_name) = &#field_name {
+                #checks
+            }
+        }
+    } else if acc.state == IxAccountState::InitIfNeeded {
+        quote! {
+            if let InitIfNeeded::Initialized(#field_name) = &#field_name {
+                #checks
+            }
+        }
+    } else {
+        checks
+    }
+}
+
+/// Rewrites field accesses on loaded accounts in a constraint expression,
+/// `claim.amount_acquired` becoming `claim.as_ref().amount_acquired`, and
+/// records the names the expression references.
+struct PayloadAccess {
+    accounts: Vec<syn::Ident>,
+    /// The names the expression references.
+    referenced: Vec<syn::Ident>,
+}
+
+impl PayloadAccess {
+    /// Accounts with a payload. Optional and init_if_needed accounts are only
+    /// known to be loaded within their own checks.
+    fn new(acc: &InstructionAccount, accounts: &[InstructionAccount]) -> Self {
+        let accounts = accounts
+            .iter()
+            .filter(|other| {
+                (other.payload.is_some() || other.token.is_some())
+                    && (!other.optional || other.id == acc.id)
+                    && match other.state {
+                        IxAccountState::Create => false,
+                        IxAccountState::InitIfNeeded => other.id == acc.id,
+                        IxAccountState::Immutable | IxAccountState::Mutable => true,
+                    }
+            })
+            .map(|other| str_to_field_name(&other.name))
+            .collect();
+        PayloadAccess {
+            accounts,
+            referenced: Vec::new(),
+        }
+    }
+}
+
+impl VisitMut for PayloadAccess {
+    fn visit_expr_path_mut(&mut self, node: &mut syn::ExprPath) {
+        if let Some(ident) = node.path.get_ident() {
+            self.referenced.push(ident.clone());
+        }
+        syn::visit_mut::visit_expr_path_mut(self, node);
+    }
+
+    fn visit_expr_field_mut(&mut self, node: &mut syn::ExprField) {
+        if let syn::Expr::Path(path) = &*node.base {
+            if path
+                .path
+                .get_ident()
+                .is_some_and(|ident| self.accounts.contains(ident))
+            {
+                let base = &node.base;
+                *node.base = syn::parse_quote! { #base.as_ref() };
+            }
+        }
+        syn::visit_mut::visit_expr_field_mut(self, node);
+    }
+}
+
+fn gen_account<D>(program_name: &str, account: &Account, checked_setters: bool) -> TokenStream
 where
     D: DiscriminatorGen,
     D::Seed: quote::ToTokens,
@@ -268,16 +1187,280 @@ where
                 .map(|_| quote! { pub bump: u8}),
         );
     let discriminator_seed = D::account_seed(program_name, account);
-    let discriminator_size = account.discriminator.as_ref().map(|d| d.size).unwrap_or(4) as usize; // TODO: FIX
+    let discriminator_size = account.discriminator_size() as usize;
 
     // TODO don't add discriminator if not defined
 
+    let signer = account
+        .seed
+        .as_ref()
+        .map(|seed| gen_account_signer(&account_name, discriminator_size, seed));
+    let setters = checked_setters.then(|| gen_checked_setters(&account_name, account));
+
     quote! {
         #[derive(BorshSerialize, BorshDeserialize, AccountDataConfig)]
         #[account_data(hash(seed = #discriminator_seed,  size = #discriminator_size))]
         pub struct #account_name {
             #( #account_fields, )*
         }
+
+        #signer
+        #setters
+    }
+}
+
+/// Generates `checked_add_<field>` and `checked_sub_<field>` for the integer
+/// fields of the account, returning an error instead of overflowing.
+fn gen_checked_setters(account_name: &syn::Ident, account: &Account) -> Option<TokenStream> {
+    let setters = account
+        .fields
+        .iter()
+        .filter(|field| field.ty.is_integer())
+        .flat_map(|field| {
+            let field_name = str_to_field_name(&field.name);
+            let ty = gen_type(&field.ty);
+            let ops = [
+                ("add", quote! { checked_add_field }),
+                ("sub", quote! { checked_sub_field }),
+            ];
+            ops.map(|(op, checked_op)| {
+                let setter = quote::format_ident!("checked_{}_{}", op, field_name);
+                quote! {
+                    #[must_use = "the field is left unchanged on overflow"]
+                    pub fn #setter(&mut self, value: #ty) -> Result<(), ProgramError> {
+                        sol_ez::math::CheckedField::#checked_op(&mut self.#field_name, value)
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    if setters.is_empty() {
+        return None;
+    }
+    Some(quote! {
+        impl #account_name {
+            #( #setters )*
+        }
+    })
+}
+
+/// Generates `seeds`, returning the seeds of the account address without the
+/// bump, and `signer`, wrapping a loaded account of this type so it can sign a
+/// CPI with its seeds.
+fn gen_account_signer(
+    account_name: &syn::Ident,
+    discriminator_size: usize,
+    seed: &AccountSeed,
+) -> TokenStream {
+    let mut inputs: Vec<syn::Ident> = Vec::new();
+    let seeds = seed
+        .seeds
+        .iter()
+        .map(|seed| match seed {
+            Seed::Defined(s) => {
+                let s = proc_macro2::Literal::byte_string(s.as_bytes());
+                quote! { #s.as_slice() }
+            }
+            Seed::Input(name) => {
+                let name = str_to_field_name(name);
+                if !inputs.contains(&name) {
+                    inputs.push(name.clone());
+                }
+                quote! { #name.as_slice() }
+            }
+        })
+        .collect::<Vec<_>>();
+    let seeds_len = seeds.len();
+    let account_type = quote! { AccountData<#discriminator_size, #account_name> };
+
+    let (bump_param, bump, read_bound) = if seed.bump {
+        (
+            None,
+            quote! { account.as_ref().bump },
+            Some(quote! { where P: AccountRead }),
+        )
+    } else {
+        (Some(quote! { bump: u8, }), quote! { bump }, None)
+    };
+
+    // a single input is left to lifetime elision
+    let seeds_fn = if inputs.len() == 1 {
+        quote! { pub fn seeds(#( #inputs: &Pubkey ),*) -> [&[u8]; #seeds_len] }
+    } else {
+        quote! { pub fn seeds<'a>(#( #inputs: &'a Pubkey ),*) -> [&'a [u8]; #seeds_len] }
+    };
+
+    quote! {
+        impl #account_name {
+            #seeds_fn {
+                [#( #seeds ),*]
+            }
+
+            pub fn signer<'a, 'info, P>(
+                account: &'a mut Account<'info, #account_type, P, Unsigned>,
+                #( #inputs: &'a Pubkey, )*
+                #bump_param
+            ) -> sol_ez::cpi::PdaSigner<'a, 'info, #account_type, P, #seeds_len>
+            #read_bound
+            {
+                let bump = #bump;
+                sol_ez::cpi::PdaSigner::new(account, Self::seeds(#( #inputs ),*), bump)
+            }
+        }
+    }
+}
+
+/// Generates the `cpi` module, calling each instruction of the program from
+/// another program.
+fn gen_cpi(idl: &MyIdl) -> TokenStream {
+    let ix_fns = idl.instructions.iter().map(|ix| {
+        // variable sized args are serialized into a bounded buffer and only
+        // the written part is passed on
+        let args_size = ix
+            .args
+            .iter()
+            .map(|arg| arg.ty.size())
+            .sum::<Option<usize>>();
+        let data_size = match args_size {
+            Some(args_size) => {
+                let data_size = idl.instruction_discriminator_size + args_size;
+                quote! { #data_size }
+            }
+            None => {
+                let discriminator_size = idl.instruction_discriminator_size;
+                quote! { #discriminator_size + sol_ez::cpi::MAX_VARIABLE_ARGS_LEN }
+            }
+        };
+
+        let fn_name = str_to_field_name(&ix.name);
+        let discriminator_name = str_to_const_name(&ix.name);
+
+        let account_params = ix.accounts.iter().map(|acc| {
+            let name = str_to_field_name(&acc.name);
+            let ty = match acc.known {
+                Some(known) => gen_known_account(known),
+                None => match (acc.state, acc.is_signed) {
+                    (IxAccountState::Immutable, false) => quote! { impl CpiAccount<'info> },
+                    (IxAccountState::Immutable, true) => quote! { impl CpiSigner<'info> },
+                    (_, false) => quote! { impl CpiWritable<'info> },
+                    (_, true) => quote! { (impl CpiWritable<'info> + CpiSigner<'info>) },
+                },
+            };
+            match acc.optional {
+                true => quote! { #name: Option<&mut #ty> },
+                false => quote! { #name: &mut #ty },
+            }
+        });
+        // optional accounts that are not passed are replaced by the program
+        // account
+        let has_optional = ix.accounts.iter().any(|acc| acc.optional);
+        let program_param = has_optional.then(|| quote! { program: &impl CpiAccount<'info>, });
+        let remaining_param = ix.remaining.as_ref().map(|remaining| {
+            let ty = match remaining.is_mutable {
+                true => quote! { impl CpiWritable<'info> },
+                false => quote! { impl CpiAccount<'info> },
+            };
+            quote! { remaining: &mut [#ty], }
+        });
+        let arg_params = ix.args.iter().map(|arg| {
+            let name = str_to_field_name(&arg.name);
+            let ty = gen_type(&arg.ty);
+            quote! { #name: #ty }
+        });
+        let args = ix.args.iter().map(|arg| str_to_field_name(&arg.name));
+        let metas = ix.accounts.iter().map(|acc| {
+            let name = str_to_field_name(&acc.name);
+            let meta = match (acc.state, acc.is_signed) {
+                _ if acc.known.is_some() => quote! { readonly },
+                (IxAccountState::Immutable, false) => quote! { readonly },
+                (IxAccountState::Immutable, true) => quote! { readonly_signer },
+                (_, false) => quote! { writable },
+                (_, true) => quote! { writable_signer },
+            };
+            match acc.optional {
+                true => quote! {
+                    match #name {
+                        Some(#name) => CpiMeta::#meta(#name),
+                        None => CpiMeta::placeholder(&ID, program)?,
+                    }
+                },
+                false => quote! { CpiMeta::#meta(#name) },
+            }
+        });
+        let returns = gen_returns(ix);
+        let data = match args_size {
+            Some(_) => quote! { &data },
+            None => quote! { &data[..#discriminator_name.len() + args_len] },
+        };
+        let invoke = match &ix.remaining {
+            Some(remaining) => {
+                let writable = remaining.is_mutable;
+                quote! {
+                    sol_ez::cpi::invoke_with_remaining(
+                        &ID,
+                        [#( #metas ),*],
+                        remaining,
+                        #writable,
+                        #data,
+                    )
+                }
+            }
+            None => quote! { sol_ez::cpi::invoke(&ID, [#( #metas ),*], #data) },
+        };
+        let invoke = match &ix.returns {
+            Some(_) => quote! {
+                #invoke?;
+                sol_ez::cpi::get_return_data(&ID)
+            },
+            None => invoke,
+        };
+        let serialize_args = match args_size {
+            _ if ix.args.is_empty() => None,
+            Some(_) => Some(quote! {
+                sol_ez::cpi::serialize_args(
+                    &mut data[#discriminator_name.len()..],
+                    &( #( #args, )* ),
+                )?;
+            }),
+            None => Some(quote! {
+                let args_len = sol_ez::cpi::serialize_variable_args(
+                    &mut data[#discriminator_name.len()..],
+                    &( #( #args, )* ),
+                )?;
+            }),
+        };
+
+        quote! {
+            #[allow(clippy::too_many_arguments)]
+            pub fn #fn_name<'info>(
+                #( #account_params, )*
+                #program_param
+                #remaining_param
+                #( #arg_params, )*
+            ) -> Result<#returns, ProgramError> {
+                let mut data = [0; #data_size];
+                data[..#discriminator_name.len()].copy_from_slice(&#discriminator_name);
+                #serialize_args
+                #invoke
+            }
+        }
+    });
+
+    quote! {
+        pub mod cpi {
+            use super::*;
+            use sol_ez::cpi::{CpiAccount, CpiMeta, CpiSigner, CpiWritable};
+
+            #( #ix_fns )*
+        }
+    }
+}
+
+/// The type an instruction handler returns, `()` when it returns no data.
+fn gen_returns(ix: &Instruction) -> TokenStream {
+    match &ix.returns {
+        Some(ty) => gen_type(ty),
+        None => quote! { () },
     }
 }
 
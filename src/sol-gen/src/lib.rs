@@ -1,16 +1,33 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{Read, Write},
+    path::Path,
+    str::FromStr,
 };
 
 use anyhow::Context;
-use codegen::contract::gen_from_config;
+use codegen::contract::gen_from_idl;
 use serde::Deserialize;
-use sol_gen_common::{config, error::SolGenError};
+use sol_gen_common::{config, error::SolGenError, lock::Lock, my_idl::MyIdl};
 
 pub mod codegen;
 pub mod idl;
 
+/// Set to regenerate a contract even though it breaks the recorded layout.
+const ALLOW_BREAKING_ENV: &str = "SOL_GEN_ALLOW_BREAKING";
+
+/// The cfg `sol_ez::program!` selects the entrypoint of the program with.
+const ENTRYPOINT_CFG: &str = "sol_ez_entrypoint";
+
+/// Set to override the entrypoint of the contract, e.g. to compare the compute
+/// units of the entrypoints.
+const ENTRYPOINT_ENV: &str = "SOL_EZ_ENTRYPOINT";
+
+const LOCK_HEADER: &str = concat!(
+    "# This file is generated by sol-gen and records the wire layout of the program.\n",
+    "# Commit it alongside the contract config.\n\n",
+);
+
 pub fn generate2(src_path: &str, out_path: &str) -> Result<(), SolGenError> {
     let mut fp = File::open(src_path)?;
     let mut src = String::new();
@@ -20,7 +37,27 @@ pub fn generate2(src_path: &str, out_path: &str) -> Result<(), SolGenError> {
         .context("failed to parse config")?;
     config.validate()?;
 
-    let code = gen_from_config(config)?;
+    println!(
+        "cargo:rustc-check-cfg=cfg({}, values(\"standard\", \"lazy\", \"no_allocator\"))",
+        ENTRYPOINT_CFG
+    );
+    println!("cargo:rerun-if-env-changed={}", ENTRYPOINT_ENV);
+    let entrypoint = match std::env::var(ENTRYPOINT_ENV) {
+        Ok(entrypoint) => config::Entrypoint::from_str(&entrypoint).map_err(anyhow::Error::msg)?,
+        Err(_) => config.program.entrypoint,
+    };
+    println!("cargo:rustc-cfg={}=\"{}\"", ENTRYPOINT_CFG, entrypoint);
+
+    let idl: MyIdl = config.into();
+
+    let lock_path = Path::new(src_path).with_extension("lock");
+    println!("cargo:rerun-if-changed={}", src_path);
+    println!("cargo:rerun-if-changed={}", lock_path.display());
+    println!("cargo:rerun-if-env-changed={}", ALLOW_BREAKING_ENV);
+
+    update_lock(&lock_path, &idl)?;
+
+    let code = gen_from_idl(&idl)?;
 
     let code_file = syn::parse2(code).context("failed to parse token stream")?;
     let code_src = prettyplease::unparse(&code_file);
@@ -30,3 +67,49 @@ pub fn generate2(src_path: &str, out_path: &str) -> Result<(), SolGenError> {
 
     Ok(())
 }
+
+/// Compares the layout of `idl` against the lock file at `lock_path` and
+/// records the new layout.
+///
+/// Breaking changes fail generation unless `SOL_GEN_ALLOW_BREAKING` is set.
+fn update_lock(lock_path: &Path, idl: &MyIdl) -> Result<(), SolGenError> {
+    let new = Lock::from_idl(idl);
+
+    if lock_path.exists() {
+        let src = fs::read_to_string(lock_path)?;
+        let old = Lock::deserialize(toml::Deserializer::new(&src))
+            .context("failed to parse lock file")?;
+        if old == new {
+            return Ok(());
+        }
+        let changes = old.diff(&new);
+        let allow_breaking = std::env::var_os(ALLOW_BREAKING_ENV).is_some();
+        if !allow_breaking && changes.iter().any(|change| change.breaking) {
+            let diff = changes
+                .iter()
+                .map(|change| format!("  {}", change))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(anyhow::anyhow!(
+                "breaking changes to the layout recorded in {}:\n{}\nset {}=1 to accept them",
+                lock_path.display(),
+                diff,
+                ALLOW_BREAKING_ENV
+            )
+            .into());
+        }
+        for change in changes {
+            warn(&change.to_string());
+        }
+    }
+
+    let lock_src = toml::to_string_pretty(&new).context("failed to serialize lock file")?;
+    fs::write(lock_path, format!("{}{}", LOCK_HEADER, lock_src))?;
+
+    Ok(())
+}
+
+/// Emits a warning from the build script calling the generator.
+fn warn(msg: &str) {
+    println!("cargo:warning={}", msg);
+}
@@ -8,4 +8,6 @@ pub enum LogParseError {
     Int(#[from] ParseIntError),
     #[error(transparent)]
     Base64(#[from] base64::DecodeError),
+    #[error(transparent)]
+    Borsh(#[from] borsh::io::Error),
 }
@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
 use base64::{Engine, prelude::BASE64_STANDARD};
+use borsh::BorshDeserialize;
 use solana_pubkey::Pubkey;
 
 use crate::{
@@ -175,6 +176,11 @@ impl ParsedReturnLog {
             data: BASE64_STANDARD.decode(log.data)?,
         })
     }
+
+    /// Deserializes the return data of an instruction declaring `returns`.
+    pub fn decode<T: BorshDeserialize>(&self) -> Result<T> {
+        Ok(T::try_from_slice(&self.data)?)
+    }
 }
 
 // A Program Compute Unit Log
@@ -1,6 +1,10 @@
+use borsh::BorshDeserialize;
 use solana_pubkey::Pubkey;
 
-use crate::parsed_log::{ParsedCuLog, ParsedDataLog, ParsedLog, ParsedProgramLog};
+use crate::{
+    parsed_log::{ParsedCuLog, ParsedDataLog, ParsedLog, ParsedProgramLog},
+    Result,
+};
 
 use super::Log2;
 
@@ -23,6 +27,14 @@ impl ParsedStructuredLog {
         let structured_log = helper_code::ParsedStructuredLogHelper::from_logs(log2);
         structured_log.into_iter().map(Self::from).collect()
     }
+
+    /// Deserializes the return data of an instruction declaring `returns`,
+    /// `None` if the program returned no data.
+    pub fn decode_return_data<T: BorshDeserialize>(&self) -> Option<Result<T>> {
+        self.return_data
+            .as_deref()
+            .map(|data| Ok(T::try_from_slice(data)?))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
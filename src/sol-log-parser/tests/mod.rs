@@ -3,9 +3,12 @@ use sol_log_parser::{
     LogParseError, ParsedLog, ParsedStructuredLog, RawLog,
     parsed_log::{
         ParsedCuLog, ParsedDataLog, ParsedFailedLog, ParsedInvokeLog, ParsedProgramLog,
-        ParsedSuccessLog,
+        ParsedReturnLog, ParsedSuccessLog,
+    },
+    raw_log::{
+        RawCuLog, RawDataLog, RawFailedLog, RawInvokeLog, RawProgramLog, RawReturnLog,
+        RawSuccessLog,
     },
-    raw_log::{RawCuLog, RawDataLog, RawFailedLog, RawInvokeLog, RawProgramLog, RawSuccessLog},
     structured_log::parsed::ParsedProgramResult,
 };
 use solana_pubkey::Pubkey;
@@ -150,6 +153,32 @@ pub fn cu_log() {
     )
 }
 
+#[test]
+pub fn return_log() {
+    let log = "Program return: D4SghRBTyA7HQSEH89uT9LgCs1TTtrPptwuqm1sLSsns nogBAAAAAAA=";
+
+    let raw_log = RawLog::parse(log);
+    assert_eq!(
+        raw_log,
+        RawLog::Return(RawReturnLog {
+            raw: "Program return: D4SghRBTyA7HQSEH89uT9LgCs1TTtrPptwuqm1sLSsns nogBAAAAAAA=",
+            program_id: "D4SghRBTyA7HQSEH89uT9LgCs1TTtrPptwuqm1sLSsns",
+            data: "nogBAAAAAAA=",
+        })
+    );
+
+    let parsed_log = ParsedLog::from_raw(&raw_log).expect("failed to parse log");
+    let return_log = ParsedReturnLog {
+        program_id: Pubkey::from_str_const("D4SghRBTyA7HQSEH89uT9LgCs1TTtrPptwuqm1sLSsns"),
+        data: 100510u64.to_le_bytes().to_vec(),
+    };
+    assert_eq!(parsed_log, ParsedLog::Return(return_log.clone()));
+    assert_eq!(
+        return_log.decode::<u64>().expect("failed to decode"),
+        100510
+    );
+}
+
 #[test]
 fn structured_log() {
     let logs = [